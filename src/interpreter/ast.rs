@@ -19,7 +19,7 @@ impl Display for Program {
 #[allow(dead_code)]
 impl Program {
     fn token_literal(&self) -> String {
-        if let Some(exprs) = self.exprs.get(0) {
+        if let Some(exprs) = self.exprs.first() {
             return exprs.to_string();
         }
         "".to_string()
@@ -37,6 +37,7 @@ pub enum NodeType {
     CallExp(Box<CallExpression>),
     InfixExp(Box<InfixExpression>),
     IntLit(Box<IntegerLiteral>),
+    StringLit(Box<StringLiteral>),
     Ident(Box<Identifier>),
     PrefixExpr(Box<PrefixExpression>),
     AssignStmt(Box<AssignStatement>),
@@ -249,6 +250,30 @@ impl Expression for IntegerLiteral {
     fn expression_node(&self) {}
 }
 
+#[derive(Clone, Debug)]
+pub struct StringLiteral {
+    pub(crate) token: Token,
+    pub(crate) value: String,
+}
+
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+
+    fn to_string(&self) -> String {
+        format!("\"{}\"", self.value)
+    }
+
+    fn get_type(self: Box<Self>) -> NodeType {
+        NodeType::StringLit(self)
+    }
+}
+
+impl Expression for StringLiteral {
+    fn expression_node(&self) {}
+}
+
 #[derive(Debug, Clone)]
 pub struct PrefixExpression {
     pub(crate) token: Token, // The prefix token, e.g. !
@@ -1,114 +1,1872 @@
 use crate::interpreter::eval::new_error;
-use crate::interpreter::object::{BuiltinObj, Chord, Instrument, Object, Sound, Sounds};
-use crate::interpreter::object::{Null, Type};
-use crate::player::effect::Vibrato;
+use crate::interpreter::object::{BuiltinObj, Chord, Instrument, Object, Processor, Sound, Sounds};
+use crate::interpreter::object::{Duration as DurationObj, Null, Scale, Type};
+use crate::interpreter::repl::Config;
+use crate::player::arrangement::{Offset, Section, Sequence, Stack};
+use crate::player::delay::Delay;
+use crate::player::effect::{
+    AmplitudeMod, Bend, BendCurve, Drive, EffectBox, ProcessorBox, RingMod, Tremolo, Vibrato,
+};
+use crate::player::filter::{Filter, FilterEnvelope, FilterKind};
 use crate::player::instrument::{InstrumentBox, Options, Synth};
+use crate::player::mixer::Master;
 use crate::player::oscillator::AnalogSaw;
+use crate::player::pitch::Pitch;
 use crate::player::play::{PlayErr, Player};
+use crate::player::reverb::Reverb;
+use crate::player::scale::Scale as PScale;
 use crate::player::song::Song;
-use crate::player::sound::Octave;
 use crate::player::sound::{Envelope, Sound as PSound};
+use crate::player::sound::{Letter, Note, Octave};
 use crate::player::tempo::Duration;
+use crate::player::tuning::Tuning;
 use lazy_static::lazy_static;
 use log::{error, info, warn};
 use std::collections::{HashMap, VecDeque};
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 lazy_static! {
     pub static ref TEMPO: Mutex<u32> = Mutex::new(0);
+    pub static ref MASTER: Mutex<Master> = Mutex::new(Master::new());
+    pub static ref CONFIG: Mutex<Config> = Mutex::new(Config::default());
+    pub static ref TUNING: Mutex<Tuning> = Mutex::new(Tuning::equal());
     pub static ref BUILTINS: HashMap<String, BuiltinObj> = {
         let mut hm = HashMap::new();
         hm.insert("tempo".to_string(), BuiltinObj { value: tempo });
         hm.insert("play".to_string(), BuiltinObj { value: play });
         hm.insert("track".to_string(), BuiltinObj { value: track });
+        hm.insert("seq".to_string(), BuiltinObj { value: seq });
+        hm.insert("stack".to_string(), BuiltinObj { value: stack });
+        hm.insert("repeat".to_string(), BuiltinObj { value: repeat });
+        hm.insert("at".to_string(), BuiltinObj { value: at });
+        hm.insert("bar".to_string(), BuiltinObj { value: bar });
+        hm.insert("section".to_string(), BuiltinObj { value: section });
+        hm.insert("song".to_string(), BuiltinObj { value: song });
         hm.insert("vib".to_string(), BuiltinObj { value: vibrato });
+        hm.insert("trem".to_string(), BuiltinObj { value: tremolo });
+        hm.insert(
+            "am".to_string(),
+            BuiltinObj {
+                value: amplitude_mod,
+            },
+        );
+        hm.insert("ring".to_string(), BuiltinObj { value: ring_mod });
+        hm.insert("legato".to_string(), BuiltinObj { value: legato });
+        hm.insert("glide".to_string(), BuiltinObj { value: glide });
+        hm.insert("bend".to_string(), BuiltinObj { value: bend });
+        hm.insert("slide".to_string(), BuiltinObj { value: slide });
+        hm.insert("hammer".to_string(), BuiltinObj { value: hammer });
+        hm.insert("pull".to_string(), BuiltinObj { value: hammer });
+        hm.insert("pan".to_string(), BuiltinObj { value: pan });
+        hm.insert("gain".to_string(), BuiltinObj { value: gain });
+        hm.insert("mute".to_string(), BuiltinObj { value: mute });
+        hm.insert("volume".to_string(), BuiltinObj { value: volume });
+        hm.insert("limit".to_string(), BuiltinObj { value: limit });
+        hm.insert("fx".to_string(), BuiltinObj { value: fx });
+        hm.insert("drive".to_string(), BuiltinObj { value: drive });
+        hm.insert("reverb".to_string(), BuiltinObj { value: reverb });
+        hm.insert("master".to_string(), BuiltinObj { value: master });
+        hm.insert("delay".to_string(), BuiltinObj { value: delay });
+        hm.insert("filter".to_string(), BuiltinObj { value: filter });
+        hm.insert("arp".to_string(), BuiltinObj { value: arp });
+        hm.insert("transpose".to_string(), BuiltinObj { value: transpose });
+        hm.insert("maj".to_string(), BuiltinObj { value: maj });
+        hm.insert("min".to_string(), BuiltinObj { value: min });
+        hm.insert("dim".to_string(), BuiltinObj { value: dim });
+        hm.insert("cresc".to_string(), BuiltinObj { value: cresc });
+        hm.insert("decresc".to_string(), BuiltinObj { value: decresc });
+        hm.insert("stac".to_string(), BuiltinObj { value: staccato });
+        hm.insert("ten".to_string(), BuiltinObj { value: tenuto });
+        hm.insert("acc".to_string(), BuiltinObj { value: accent });
+        hm.insert("ferm".to_string(), BuiltinObj { value: fermata });
+        hm.insert("aug".to_string(), BuiltinObj { value: aug });
+        hm.insert("sus2".to_string(), BuiltinObj { value: sus2 });
+        hm.insert("sus4".to_string(), BuiltinObj { value: sus4 });
+        hm.insert("maj7".to_string(), BuiltinObj { value: maj7 });
+        hm.insert("min7".to_string(), BuiltinObj { value: min7 });
+        hm.insert("dom7".to_string(), BuiltinObj { value: dom7 });
+        hm.insert("dim7".to_string(), BuiltinObj { value: dim7 });
+        hm.insert("add9".to_string(), BuiltinObj { value: add9 });
+        hm.insert("scale".to_string(), BuiltinObj { value: scale });
+        hm.insert("degree".to_string(), BuiltinObj { value: degree });
+        hm.insert("pitch".to_string(), BuiltinObj { value: pitch });
+        hm.insert("tuning".to_string(), BuiltinObj { value: tuning });
+
+        hm
+    };
+}
+
+fn vibrato(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 3 {
+        return new_error(
+            "expecting at least 1 (note, sound, track), depth and phase for vibrato".to_string(),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let speed_el = match args.pop_front() {
+        Some(a) => a,
+        None => {
+            return new_error(
+                "expecting at least 1 (note, sound, track), speed and depth for vibrato"
+                    .to_string(),
+                line,
+            )
+        }
+    };
+    let depth_el = match args.pop_front() {
+        Some(a) => a,
+        None => {
+            return new_error(
+                "expecting at least 1 (note, sound, track), speed and depth for vibrato"
+                    .to_string(),
+                line,
+            )
+        }
+    };
+
+    let speed_ins = speed_el.inspect();
+    let speed = match speed_el.get_type() {
+        Type::Int(i) => i,
+        _ => {
+            return new_error(
+                format!("invalid speed: expected integer, got {}", speed_ins),
+                line,
+            )
+        }
+    };
+
+    let depth_ins = depth_el.inspect();
+    let depth = match depth_el.get_type() {
+        Type::Int(i) => i,
+        _ => {
+            return new_error(
+                format!("invalid phase: expected integer, got {}", depth_ins),
+                line,
+            )
+        }
+    };
+
+    with_effect(
+        args,
+        Box::new(Vibrato::new(depth as f32, speed as f32)),
+        line,
+    )
+}
+
+// trem(rate, depth, notes or track)
+fn tremolo(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 3 {
+        return new_error(
+            "expecting rate, depth and at least 1 (note, sound, track) for tremolo".to_string(),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let rate_el = args.pop_front().unwrap();
+    let rate_info = rate_el.inspect();
+    let rate = match rate_el.get_type() {
+        Type::Duration(d) => d.get_dur(),
+        _ => {
+            return new_error(
+                format!(
+                    "invalid tremolo rate: expected note value like d8, got {}",
+                    rate_info
+                ),
+                line,
+            )
+        }
+    };
+    let depth = match to_percent(args.pop_front().unwrap(), "depth", line) {
+        Ok(d) => d,
+        Err(e) => return e,
+    };
+
+    with_effect(args, Box::new(Tremolo::new(rate, depth)), line)
+}
+
+// am(frequency, depth, notes or track)
+fn amplitude_mod(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let (hz, depth, args) = match modulation_args(args, "amplitude modulation", line) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    with_effect(args, Box::new(AmplitudeMod::new(hz, depth)), line)
+}
+
+// ring(frequency, mix, notes or track)
+fn ring_mod(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let (hz, mix, args) = match modulation_args(args, "ring modulation", line) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    with_effect(args, Box::new(RingMod::new(hz, mix)), line)
+}
+
+type ModulationArgs = (f32, f32, VecDeque<Box<dyn Object>>);
+
+// Splits the frequency in Hz and the amount in percent from the notes to modulate.
+fn modulation_args(
+    args: Vec<Box<dyn Object + 'static>>,
+    name: &str,
+    line: usize,
+) -> Result<ModulationArgs, Box<dyn Object>> {
+    if args.len() < 3 {
+        return Err(new_error(
+            format!(
+                "expecting frequency, amount and at least 1 (note, sound, track) for {}",
+                name
+            ),
+            line,
+        ));
+    }
+    let mut args = VecDeque::from(args);
+
+    let hz_el = args.pop_front().unwrap();
+    let hz_info = hz_el.inspect();
+    let hz = match hz_el.get_type() {
+        Type::Int(i) if i > 0 => i as f32,
+        _ => {
+            return Err(new_error(
+                format!(
+                    "invalid {} frequency: expected Hz as positive integer, got {}",
+                    name, hz_info
+                ),
+                line,
+            ))
+        }
+    };
+    let amount = to_percent(args.pop_front().unwrap(), "amount", line)?;
+    Ok((hz, amount, args))
+}
+
+// Adds the effect to every sound of a track, or to the given notes.
+fn with_effect(
+    args: VecDeque<Box<dyn Object + 'static>>,
+    effect: EffectBox,
+    line: usize,
+) -> Box<dyn Object> {
+    if let Some(first) = args.front() {
+        if let Type::Instrument(ins) = first.clone().get_type() {
+            if args.len() != 1 {
+                return new_error(
+                    "expecting a single track, tracks and notes cannot be mixed".to_string(),
+                    line,
+                );
+            }
+            let mut ins = ins.get_instrument();
+            ins.sounds_mut(&mut |sound| {
+                sound.push_effect(effect.clone());
+                true
+            });
+            return Box::new(Instrument::new(ins));
+        }
+    }
+
+    let mut sounds = match notes_to_sounds(Vec::from(args), line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    for chord in sounds.iter_mut() {
+        for sound in chord.iter_mut() {
+            sound.push_effect(effect.clone());
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+fn sounds_to_obj(sounds: VecDeque<Vec<PSound>>) -> Box<dyn Object> {
+    let mut sound_chords = VecDeque::with_capacity(sounds.len());
+
+    for sound in sounds {
+        let a: Vec<Sound> = sound.iter().map(|s| Sound::new(s.clone(), false)).collect();
+        sound_chords.push_back(Chord::new(a));
+    }
+
+    Box::new(Sounds::new(sound_chords))
+}
+
+// Every given note slides from the pitch of the note before it.
+fn legato(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let mut sounds = match notes_to_sounds(args, line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    for chord in sounds.iter_mut() {
+        for sound in chord.iter_mut() {
+            sound.legato = true;
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+// bend(note, semitones[, curve])
+fn bend(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 2 && args.len() != 3 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 or 3 (note, semitones, curve)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let note = args.pop_front().unwrap();
+
+    let semi_el = args.pop_front().unwrap();
+    let semi_info = semi_el.inspect();
+    let semitones = match semi_el.get_type() {
+        Type::Int(i) => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid bend: expected semitones as integer, got {}",
+                    semi_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let curve = match args.pop_front() {
+        Some(curve_el) => {
+            let curve_info = curve_el.inspect();
+            let curve = match curve_el.get_type() {
+                Type::String(s) => BendCurve::from_str(&s),
+                _ => None,
+            };
+            match curve {
+                Some(c) => c,
+                None => {
+                    return new_error(
+                        format!(
+                            "invalid bend curve: expected \"lin\", \"exp\" or \"log\", got {}",
+                            curve_info
+                        ),
+                        line,
+                    )
+                }
+            }
+        }
+        None => BendCurve::Logarithmic,
+    };
+
+    let mut sounds = match notes_to_sounds(vec![note], line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+    for chord in sounds.iter_mut() {
+        for sound in chord.iter_mut() {
+            sound.push_effect(Box::new(Bend::new(semitones as f32, curve)));
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+// Only the first note is picked, the following ones slide into place.
+fn slide(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let mut sounds = match notes_to_sounds(args, line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    for chord in sounds.iter_mut().skip(1) {
+        for sound in chord.iter_mut() {
+            sound.legato = true;
+            sound.attack = false;
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+// Hammer-ons and pull-offs: only the first note is picked,
+// the following ones change pitch without a new attack.
+fn hammer(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let mut sounds = match notes_to_sounds(args, line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    for chord in sounds.iter_mut().skip(1) {
+        for sound in chord.iter_mut() {
+            sound.attack = false;
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+// arp(chord, pattern, rate[, octaves])
+// Plays the notes of the chord one at a time for as long as the chord lasts.
+fn arp(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 3 && args.len() != 4 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=3 or 4 (chord, pattern, rate, octaves)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let chord_el = args.pop_front().unwrap();
+    let chord_info = chord_el.inspect();
+    let mut notes: Vec<PSound> = match chord_el.get_type() {
+        Type::Chord(c) => c.get_sounds().into_iter().map(|s| s.sound).collect(),
+        _ => {
+            return new_error(
+                format!("invalid arp: expected chord, got {}", chord_info),
+                line,
+            )
+        }
+    };
+    let chord_beats = notes
+        .iter()
+        .map(|s| s.duration.to_beats())
+        .fold(0.0, f32::max);
+    notes.retain(|s| s.pitch.is_some());
+    notes.sort_by_key(|s| s.pitch.map(|p| p.key()));
+    if notes.is_empty() {
+        return new_error("invalid arp: the chord has no notes".to_string(), line);
+    }
+
+    let pattern_el = args.pop_front().unwrap();
+    let pattern_info = pattern_el.inspect();
+    let pattern = match pattern_el.get_type() {
+        Type::String(s) if ["up", "down", "updown", "random"].contains(&s.as_str()) => s,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid arp pattern: expected up, down, updown or random, got {}",
+                    pattern_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let rate_el = args.pop_front().unwrap();
+    let rate_info = rate_el.inspect();
+    let rate = match rate_el.get_type() {
+        Type::Duration(d) => d.get_dur(),
+        _ => {
+            return new_error(
+                format!(
+                    "invalid arp rate: expected note value like d16, got {}",
+                    rate_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let octaves = match args.pop_front() {
+        Some(oct_el) => {
+            let oct_info = oct_el.inspect();
+            match oct_el.get_type() {
+                Type::Int(i) if (1..=4).contains(&i) => i,
+                _ => {
+                    return new_error(
+                        format!(
+                            "invalid arp octaves: expected integer between 1 and 4, got {}",
+                            oct_info
+                        ),
+                        line,
+                    )
+                }
+            }
+        }
+        None => 1,
+    };
+
+    let mut pool = Vec::with_capacity(notes.len() * octaves as usize);
+    for oct in 0..octaves {
+        for note in notes.iter() {
+            let mut sound = match note.transposed(oct * 12) {
+                Some(s) => s,
+                None => {
+                    return new_error(
+                        "invalid arp: octaves go above the highest octave".to_string(),
+                        line,
+                    )
+                }
+            };
+            sound.duration = rate.clone();
+            pool.push(sound);
+        }
+    }
+
+    let order: Vec<usize> = match pattern.as_str() {
+        "up" => (0..pool.len()).collect(),
+        "down" => (0..pool.len()).rev().collect(),
+        // the top and bottom notes are not repeated when turning around
+        "updown" => (0..pool.len())
+            .chain((1..pool.len().saturating_sub(1)).rev())
+            .collect(),
+        _ => vec![],
+    };
+
+    let steps = ((chord_beats / rate.to_beats()) as usize).max(1);
+    let mut rng = XorShift::from_time();
+    let mut sounds = VecDeque::with_capacity(steps);
+    for step in 0..steps {
+        let i = match order.is_empty() {
+            true => rng.next() as usize % pool.len(),
+            false => order[step % order.len()],
+        };
+        sounds.push_back(vec![pool[i].clone()]);
+    }
+
+    sounds_to_obj(sounds)
+}
+
+// transpose(note, chord, notes, scale or track, semitones)
+fn transpose(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 (note, chord, notes, scale or track, semitones)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let target = args.pop_front().unwrap();
+
+    let semi_el = args.pop_front().unwrap();
+    let semi_info = semi_el.inspect();
+    let semitones = match semi_el.get_type() {
+        Type::Int(i) => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid transpose: expected semitones as integer, got {}",
+                    semi_info
+                ),
+                line,
+            )
+        }
+    };
+    let out_of_range = || {
+        new_error(
+            format!(
+                "invalid transpose: {} semitones go beyond the available octaves",
+                semitones
+            ),
+            line,
+        )
+    };
+
+    let transpose_chord = |chord: Chord| -> Option<Chord> {
+        let mut sounds = chord.get_sounds();
+        for sound in sounds.iter_mut() {
+            sound.sound = sound.sound.transposed(semitones)?;
+        }
+        Some(Chord::new(sounds))
+    };
+
+    let target_info = target.inspect();
+    match target.get_type() {
+        Type::Sound(sound) => match sound.sound.transposed(semitones) {
+            Some(s) => Box::new(Sound::new(s, sound.modified)),
+            None => out_of_range(),
+        },
+        Type::Chord(chord) => match transpose_chord(chord) {
+            Some(c) => Box::new(c),
+            None => out_of_range(),
+        },
+        Type::Sounds(sounds) => {
+            let mut chords = VecDeque::new();
+            for chord in sounds.get_sounds() {
+                match transpose_chord(chord) {
+                    Some(c) => chords.push_back(c),
+                    None => return out_of_range(),
+                }
+            }
+            Box::new(Sounds::new(chords))
+        }
+        Type::Scale(scale) => match scale.get_scale().transposed(semitones) {
+            Some(s) => Box::new(Scale::new(s)),
+            None => out_of_range(),
+        },
+        Type::Instrument(ins) => {
+            let mut ins = ins.get_instrument();
+            let in_range = ins.sounds_mut(&mut |sound| match sound.transposed(semitones) {
+                Some(s) => {
+                    *sound = s;
+                    true
+                }
+                None => false,
+            });
+            match in_range {
+                true => Box::new(Instrument::new(ins)),
+                false => out_of_range(),
+            }
+        }
+        _ => new_error(
+            format!(
+                "invalid transpose: expected note, chord, notes, scale or track, got {}",
+                target_info
+            ),
+            line,
+        ),
+    }
+}
+
+fn maj(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 4, 7], line)
+}
+
+fn min(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 3, 7], line)
+}
+
+// dim(note[, inversion]) for the chord, dim(from, to[, curve], notes...) for the hairpin
+fn dim(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let levels = args
+        .first()
+        .map(|first| matches!(first.clone().get_type(), Type::Dynamic(_) | Type::Int(_)));
+    match levels {
+        Some(true) => hairpin(args, false, line),
+        _ => named_chord(args, &[0, 3, 6], line),
+    }
+}
+
+// cresc(from, to[, curve], notes...)
+fn cresc(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    hairpin(args, true, line)
+}
+
+// decresc(from, to[, curve], notes...), the same as the dim hairpin
+fn decresc(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    hairpin(args, false, line)
+}
+
+// Sets the velocity of the notes from `from` to `to` following their durations,
+// the last note reaches `to`.
+fn hairpin(args: Vec<Box<dyn Object + 'static>>, louder: bool, line: usize) -> Box<dyn Object> {
+    if args.len() < 3 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want at least 3 (from, to, curve, notes)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let mut levels = Vec::with_capacity(2);
+    for _ in 0..2 {
+        let level_el = args.pop_front().unwrap();
+        let level_info = level_el.inspect();
+        match level_el.get_type() {
+            Type::Dynamic(d) => levels.push(d.velocity as f32),
+            Type::Int(i) if (1..=127).contains(&i) => levels.push(i as f32),
+            _ => {
+                return new_error(
+                    format!(
+                    "invalid level: expected dynamic like pp or velocity between 1 and 127, got {}",
+                    level_info
+                ),
+                    line,
+                )
+            }
+        }
+    }
+    let (from, to) = (levels[0], levels[1]);
+    if louder && to < from || !louder && to > from {
+        return new_error(
+            format!(
+                "invalid levels: a {} goes from {} to {}",
+                if louder { "crescendo" } else { "decrescendo" },
+                from,
+                to
+            ),
+            line,
+        );
+    }
+
+    let mut exponential = false;
+    if let Some(curve_el) = args.front() {
+        if let Type::String(curve) = curve_el.clone().get_type() {
+            exponential = match curve.as_str() {
+                "lin" => false,
+                "exp" => true,
+                _ => {
+                    return new_error(
+                        format!("invalid curve: expected \"lin\" or \"exp\", got {}", curve),
+                        line,
+                    )
+                }
+            };
+            args.pop_front();
+        }
+    }
+
+    let mut sounds = match notes_to_sounds(Vec::from(args), line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    // every chord lasts as long as its first note
+    let starts: Vec<f32> = sounds
+        .iter()
+        .scan(0.0, |beats, chord| {
+            let start = *beats;
+            *beats += chord.first().map_or(0.0, |s| s.duration.to_beats());
+            Some(start)
+        })
+        .collect();
+    let last_start = starts.last().cloned().unwrap_or(0.0);
+
+    for (chord, start) in sounds.iter_mut().zip(starts) {
+        let t = match last_start > 0.0 {
+            true => start / last_start,
+            false => 0.0,
+        };
+        let level = match exponential {
+            true => from * (to / from).powf(t),
+            false => from + (to - from) * t,
+        };
+        for sound in chord.iter_mut() {
+            sound.velocity = Some(level.round() as u8);
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+fn aug(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 4, 8], line)
+}
+
+fn sus2(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 2, 7], line)
+}
+
+fn sus4(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 5, 7], line)
+}
+
+fn maj7(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 4, 7, 11], line)
+}
+
+fn min7(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 3, 7, 10], line)
+}
+
+fn dom7(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 4, 7, 10], line)
+}
+
+fn dim7(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 3, 6, 9], line)
+}
+
+fn add9(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    named_chord(args, &[0, 4, 7, 14], line)
+}
+
+// Builds the chord on the given root, the same way `c_4_4 + e + g` does.
+// An inversion moves that many of the lowest notes an octave up.
+fn named_chord(
+    args: Vec<Box<dyn Object + 'static>>,
+    intervals: &[i32],
+    line: usize,
+) -> Box<dyn Object> {
+    if args.len() != 1 && args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=1 or 2 (root, inversion)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let root_el = args.pop_front().unwrap();
+    let root_info = root_el.inspect();
+    let root = match root_el.get_type() {
+        Type::Sound(s) => s.get_sound(),
+        _ => {
+            return new_error(
+                format!(
+                    "invalid chord root: expected note with octave and duration, got {}",
+                    root_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let inversion = match args.pop_front() {
+        Some(inv_el) => {
+            let inv_info = inv_el.inspect();
+            match inv_el.get_type() {
+                Type::Int(i) if i >= 0 && (i as usize) < intervals.len() => i as usize,
+                _ => {
+                    return new_error(
+                        format!(
+                            "invalid inversion: expected integer between 0 and {}, got {}",
+                            intervals.len() - 1,
+                            inv_info
+                        ),
+                        line,
+                    )
+                }
+            }
+        }
+        None => 0,
+    };
+
+    let mut intervals = intervals.to_vec();
+    for interval in intervals.iter_mut().take(inversion) {
+        *interval += 12;
+    }
+    intervals.rotate_left(inversion);
+
+    let mut chord = Vec::with_capacity(intervals.len());
+    for (i, interval) in intervals.into_iter().enumerate() {
+        match root.transposed(interval) {
+            // like with `+`, only the first note carries its own octave and duration
+            Some(s) => chord.push(Sound::new(s, i > 0)),
+            None => {
+                return new_error(
+                    "invalid chord: it goes beyond the available octaves".to_string(),
+                    line,
+                )
+            }
+        }
+    }
+
+    Box::new(Chord::new(chord))
+}
+
+// scale(root, name) or scale(root, steps...)
+fn scale(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 (root, name) or more (root, steps)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let root_el = args.pop_front().unwrap();
+    let root_info = root_el.inspect();
+    let root = match root_el.get_type() {
+        Type::Note(n) => n.get_note(),
+        Type::Sound(s) => s.get_sound().note(),
+        _ => {
+            return new_error(
+                format!("invalid scale root: expected note, got {}", root_info),
+                line,
+            )
+        }
+    };
+    if let Note::Space = root {
+        return new_error("invalid scale root: a rest has no pitch".to_string(), line);
+    }
+
+    if args.len() == 1 {
+        if let Type::String(name) = args.front().unwrap().clone().get_type() {
+            return match PScale::from_name(root, &name) {
+                Some(s) => Box::new(Scale::new(s)),
+                None => new_error(format!("unknown scale `{}`", name), line),
+            };
+        }
+    }
+
+    let mut steps = Vec::with_capacity(args.len());
+    for arg in args {
+        let info = arg.inspect();
+        match arg.get_type() {
+            Type::Int(i) => steps.push(i),
+            _ => {
+                return new_error(
+                    format!(
+                        "invalid scale step: expected semitones as integer, got {}",
+                        info
+                    ),
+                    line,
+                )
+            }
+        }
+    }
+    match PScale::from_steps(root, &steps) {
+        Some(s) => Box::new(Scale::new(s)),
+        None => new_error(
+            "invalid scale steps: they must be positive and span at most an octave".to_string(),
+            line,
+        ),
+    }
+}
+
+// degree(scale, n, octave, duration)
+fn degree(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 4 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=4 (scale, degree, octave, duration)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let scale_el = args.pop_front().unwrap();
+    let scale_info = scale_el.inspect();
+    let scale = match scale_el.get_type() {
+        Type::Scale(s) => s.get_scale(),
+        _ => {
+            return new_error(
+                format!("invalid degree: expected scale, got {}", scale_info),
+                line,
+            )
+        }
+    };
+
+    let n_el = args.pop_front().unwrap();
+    let n_info = n_el.inspect();
+    let n = match n_el.get_type() {
+        Type::Int(i) if i > 0 => i as usize,
+        _ => {
+            return new_error(
+                format!("invalid degree: expected positive integer, got {}", n_info),
+                line,
+            )
+        }
+    };
+
+    let oct_el = args.pop_front().unwrap();
+    let oct_info = oct_el.inspect();
+    let octave = match oct_el.get_type() {
+        Type::Octave(o) => o.get_oct(),
+        _ => {
+            return new_error(
+                format!(
+                    "invalid degree octave: expected octave like o4, got {}",
+                    oct_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let dur_el = args.pop_front().unwrap();
+    let dur_info = dur_el.inspect();
+    let duration = match dur_el.get_type() {
+        Type::Duration(d) => d.get_dur(),
+        _ => {
+            return new_error(
+                format!(
+                    "invalid degree duration: expected note value like d8, got {}",
+                    dur_info
+                ),
+                line,
+            )
+        }
+    };
+
+    match scale.degree(n, octave, duration) {
+        Some(s) => Box::new(Sound::new(s, false)),
+        None => new_error(
+            format!("invalid degree: {} goes beyond the available octaves", n),
+            line,
+        ),
+    }
+}
+
+// Small pseudo random generator, good enough for musical choices.
+struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        // the state must never be zero
+        Self { state: nanos | 1 }
+    }
+
+    fn next(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+}
+
+// Sounds for half of its duration, the next note starts on time.
+fn staccato(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    with_articulation(args, |sound| sound.sounding = 0.5, line)
+}
+
+fn tenuto(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    with_articulation(args, |sound| sound.sounding = 1.0, line)
+}
+
+fn accent(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    with_articulation(args, |sound| sound.accent = true, line)
+}
+
+// Holds the note twice as long before moving on.
+fn fermata(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    with_articulation(args, |sound| sound.hold = 2.0, line)
+}
+
+// Changes every sound of a track, or the given notes.
+fn with_articulation(
+    args: Vec<Box<dyn Object + 'static>>,
+    articulate: fn(&mut PSound),
+    line: usize,
+) -> Box<dyn Object> {
+    if let Some(first) = args.first() {
+        if let Type::Instrument(ins) = first.clone().get_type() {
+            if args.len() != 1 {
+                return new_error(
+                    "expecting a single track, tracks and notes cannot be mixed".to_string(),
+                    line,
+                );
+            }
+            let mut ins = ins.get_instrument();
+            ins.sounds_mut(&mut |sound| {
+                articulate(sound);
+                true
+            });
+            return Box::new(Instrument::new(ins));
+        }
+    }
+
+    let mut sounds = match notes_to_sounds(args, line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+
+    for chord in sounds.iter_mut() {
+        for sound in chord.iter_mut() {
+            articulate(sound);
+        }
+    }
+
+    sounds_to_obj(sounds)
+}
+
+fn glide(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 (track, milliseconds)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let mut ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+
+    let ms_el = args.pop_front().unwrap();
+    let ms_info = ms_el.inspect();
+    let ms = match ms_el.get_type() {
+        Type::Int(i) if i >= 0 => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid glide time: expected milliseconds as positive integer, got {}",
+                    ms_info
+                ),
+                line,
+            )
+        }
+    };
+
+    ins.synths_mut(&mut |synth| synth.set_glide(ms as f32 / 1000.0));
+    Box::new(Instrument::new(ins))
+}
+
+fn to_percent(arg: Box<dyn Object>, name: &str, line: usize) -> Result<f32, Box<dyn Object>> {
+    let info = arg.inspect();
+    match arg.get_type() {
+        Type::Int(i) if (0..=100).contains(&i) => Ok(i as f32 / 100.0),
+        _ => Err(new_error(
+            format!(
+                "invalid {}: expected integer between 0 and 100, got {}",
+                name, info
+            ),
+            line,
+        )),
+    }
+}
+
+fn pan(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 (track, position)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let mut ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+    let pos_el = args.pop_front().unwrap();
+
+    let pos_info = pos_el.inspect();
+    let pos = match pos_el.get_type() {
+        Type::Int(i) if (-100..=100).contains(&i) => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid pan position: expected integer between -100 and 100, got {}",
+                    pos_info
+                ),
+                line,
+            )
+        }
+    };
+
+    ins.synths_mut(&mut |synth| synth.set_pan(pos as f32 / 100.0));
+    Box::new(Instrument::new(ins))
+}
+
+fn gain(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 (track, decibels)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let mut ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+    let db_el = args.pop_front().unwrap();
+    let db_info = db_el.inspect();
+    let db = match db_el.get_type() {
+        Type::Int(i) => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid gain: expected decibels as integer, got {}",
+                    db_info
+                ),
+                line,
+            )
+        }
+    };
+
+    ins.synths_mut(&mut |synth| synth.set_gain_db(db as f32));
+    Box::new(Instrument::new(ins))
+}
+
+fn mute(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 1 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=1 (track)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut ins = match to_instrument(args.into_iter().next().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+
+    ins.synths_mut(&mut |synth| synth.set_muted(true));
+    Box::new(Instrument::new(ins))
+}
+
+fn volume(mut args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 1 {
+        return new_error(
+            format!("wrong number of arguments. got={}, want=1", args.len()),
+            line,
+        );
+    }
+
+    let arg = args.pop().unwrap();
+    let info = arg.inspect();
+    let db = match arg.get_type() {
+        Type::Int(i) => i,
+        _ => {
+            return new_error(
+                format!("invalid volume: expected decibels as integer, got {}", info),
+                line,
+            )
+        }
+    };
+
+    let mut master = match MASTER.lock() {
+        Ok(m) => m,
+        Err(_) => panic!("cannot get master"),
+    };
+    master.set_volume_db(db as f32);
+    Box::new(Null {})
+}
+
+fn limit(mut args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 1 {
+        return new_error(
+            format!("wrong number of arguments. got={}, want=1", args.len()),
+            line,
+        );
+    }
+
+    let arg = args.pop().unwrap();
+    let info = arg.inspect();
+    let db = match arg.get_type() {
+        Type::Int(i) if i <= 0 => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid limiter threshold: expected decibels as integer up to 0, got {}",
+                    info
+                ),
+                line,
+            )
+        }
+    };
+
+    let mut master = match MASTER.lock() {
+        Ok(m) => m,
+        Err(_) => panic!("cannot get master"),
+    };
+    master.set_threshold_db(db as f32);
+    Box::new(Null {})
+}
+
+fn fx(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 2 {
+        return new_error(
+            "expecting a track followed by at least 1 effect for fx".to_string(),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let mut ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+
+    let processors = match to_processors(Vec::from(args), 1, line) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+
+    for p in processors {
+        ins.push_processor(p);
+    }
+    Box::new(Instrument::new(ins))
+}
+
+fn drive(mut args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 1 {
+        return new_error(
+            format!("wrong number of arguments. got={}, want=1", args.len()),
+            line,
+        );
+    }
+
+    let arg = args.pop().unwrap();
+    let info = arg.inspect();
+    match arg.get_type() {
+        Type::Int(i) if (0..=100).contains(&i) => {
+            Box::new(Processor::new(Box::new(Drive::new(i as f32 / 100.0))))
+        }
+        _ => new_error(
+            format!(
+                "invalid drive: expected integer between 0 and 100, got {}",
+                info
+            ),
+            line,
+        ),
+    }
+}
+
+fn reverb(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 3 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=3 (room size, damping, wet)",
+                args.len()
+            ),
+            line,
+        );
+    }
+
+    let mut params = Vec::with_capacity(args.len());
+    for arg in args {
+        let info = arg.inspect();
+        match arg.get_type() {
+            Type::Int(i) if (0..=100).contains(&i) => params.push(i as f32 / 100.0),
+            _ => {
+                return new_error(
+                    format!(
+                        "invalid reverb parameter: expected integer between 0 and 100, got {}",
+                        info
+                    ),
+                    line,
+                )
+            }
+        }
+    }
+
+    let reverb = Reverb::new(params[0], params[1], params[2]);
+    Box::new(Processor::new(Box::new(reverb)))
+}
+
+// delay(time, feedback, mix[, tone[, ping_pong]])
+fn delay(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 3 || args.len() > 5 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=3 to 5 (time, feedback, mix, tone, ping pong)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let time_el = args.pop_front().unwrap();
+    let time_info = time_el.inspect();
+    let time = match time_el.get_type() {
+        Type::Duration(d) => d.get_dur(),
+        _ => {
+            return new_error(
+                format!(
+                    "invalid delay time: expected note value like d8*, got {}",
+                    time_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let mut params = Vec::with_capacity(args.len());
+    for arg in args {
+        let info = arg.inspect();
+        match arg.get_type() {
+            Type::Int(i) if (0..=100).contains(&i) => params.push(i as f32 / 100.0),
+            _ => {
+                return new_error(
+                    format!(
+                        "invalid delay parameter: expected integer between 0 and 100, got {}",
+                        info
+                    ),
+                    line,
+                )
+            }
+        }
+    }
+
+    let feedback = params[0].min(0.95);
+    let mix = params[1];
+    // from 200Hz to roughly 20kHz
+    let cutoff = 200.0 * (params.get(2).unwrap_or(&0.5) * 6.64).exp2();
+    let ping_pong = params.len() > 3 && params[3] > 0.0;
+
+    let delay = Delay::new(time, feedback, mix, cutoff, ping_pong);
+    Box::new(Processor::new(Box::new(delay)))
+}
+
+// filter(track, kind, cutoff, resonance[, amount, attack, decay, sustain, release])
+fn filter(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 4 && args.len() != 9 && args.len() != 10 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=4 (track, kind, cutoff, resonance) \
+                 or 9 to 10 (track, kind, cutoff, resonance, amount, attack, decay, sustain, release, resonance amount)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let mut ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+
+    let kind_el = args.pop_front().unwrap();
+    let kind_info = kind_el.inspect();
+    let kind = match kind_el.get_type() {
+        Type::String(s) => FilterKind::from_str(&s),
+        _ => None,
+    };
+    let kind = match kind {
+        Some(k) => k,
+        None => {
+            return new_error(
+                format!(
+                    "invalid filter kind: expected \"lp\", \"hp\" or \"bp\", got {}",
+                    kind_info
+                ),
+                line,
+            )
+        }
+    };
+
+    let mut params = Vec::with_capacity(args.len());
+    for arg in args {
+        let info = arg.inspect();
+        match arg.get_type() {
+            Type::Int(i) => params.push(i),
+            _ => {
+                return new_error(
+                    format!("invalid filter parameter: expected integer, got {}", info),
+                    line,
+                )
+            }
+        }
+    }
 
-        hm
+    let cutoff = params[0];
+    if cutoff <= 0 {
+        return new_error(
+            format!("invalid cutoff: expected frequency in Hz, got {}", cutoff),
+            line,
+        );
+    }
+    let resonance = params[1];
+    if !(0..=100).contains(&resonance) {
+        return new_error(
+            format!(
+                "invalid resonance: expected integer between 0 and 100, got {}",
+                resonance
+            ),
+            line,
+        );
+    }
+
+    // amount in semitones, times in milliseconds, sustain from 0 to 100
+    // and the resonance amount from -100 to 100
+    let env = if params.len() > 2 {
+        if params[3..7].iter().any(|p| *p < 0) || params[5] > 100 {
+            return new_error(
+                "invalid filter envelope: expected positive times in milliseconds and sustain between 0 and 100".to_string(),
+                line,
+            );
+        }
+        let res_amount = params.get(7).copied().unwrap_or(0);
+        if !(-100..=100).contains(&res_amount) {
+            return new_error(
+                format!(
+                    "invalid resonance amount: expected integer between -100 and 100, got {}",
+                    res_amount
+                ),
+                line,
+            );
+        }
+        Some(FilterEnvelope {
+            amount: params[2] as f32,
+            attack: params[3] as f32 / 1000.0,
+            decay: params[4] as f32 / 1000.0,
+            sustain: params[5] as f32 / 100.0,
+            release: params[6] as f32 / 1000.0,
+            resonance: res_amount as f32 / 100.0,
+        })
+    } else {
+        None
     };
+
+    let filter = Filter::new(kind, cutoff as f32, resonance as f32 / 100.0, env);
+    ins.synths_mut(&mut |synth| synth.set_filter(filter.clone()));
+    Box::new(Instrument::new(ins))
 }
 
-fn vibrato(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
-    if args.len() < 3 {
+fn master(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.is_empty() {
         return new_error(
-            "expecting at least 1 (note, sound, track), depth and phase for vibrato".to_string(),
+            "expecting at least 1 effect for the master".to_string(),
+            line,
+        );
+    }
+
+    let processors = match to_processors(args, 0, line) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+
+    let mut master = match MASTER.lock() {
+        Ok(m) => m,
+        Err(_) => panic!("cannot get master"),
+    };
+    for p in processors {
+        master.push_processor(p);
+    }
+    Box::new(Null {})
+}
+
+// `offset` is the position of the first effect in the arguments, for error messages.
+fn to_processors(
+    args: Vec<Box<dyn Object + 'static>>,
+    offset: usize,
+    line: usize,
+) -> Result<Vec<ProcessorBox>, Box<dyn Object>> {
+    let mut processors = Vec::with_capacity(args.len());
+    for (i, arg) in args.into_iter().enumerate() {
+        let info = arg.inspect();
+        match arg.get_type() {
+            Type::Processor(p) => processors.push(p.get_processor()),
+            _ => {
+                return Err(new_error(
+                    format!("expected effect, argument {} is {}", i + offset, info),
+                    line,
+                ))
+            }
+        }
+    }
+    Ok(processors)
+}
+
+fn to_instrument(arg: Box<dyn Object>, line: usize) -> Result<InstrumentBox, Box<dyn Object>> {
+    let info = arg.inspect();
+    match arg.get_type() {
+        Type::Instrument(ins) => Ok(ins.get_instrument()),
+        _ => Err(new_error(
+            format!("invalid track: expected track, got {}", info),
+            line,
+        )),
+    }
+}
+
+fn track(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let ins = match notes_to_ins(args, line) {
+        Ok(s) => s,
+        Err(e) => return e,
+    };
+    Box::new(Instrument::new(ins))
+}
+
+fn to_instruments(
+    args: Vec<Box<dyn Object + 'static>>,
+    name: &str,
+    line: usize,
+) -> Result<Vec<InstrumentBox>, Box<dyn Object>> {
+    if args.is_empty() {
+        return Err(new_error(
+            format!("zero arguments given to {}, expected tracks", name),
+            line,
+        ));
+    }
+    args.into_iter().map(|a| to_instrument(a, line)).collect()
+}
+
+fn seq(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    match to_instruments(args, "seq", line) {
+        Ok(parts) => Box::new(Instrument::new(Box::new(Sequence::new(parts)))),
+        Err(e) => e,
+    }
+}
+
+fn stack(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    match to_instruments(args, "stack", line) {
+        Ok(parts) => Box::new(Instrument::new(Box::new(Stack::new(parts)))),
+        Err(e) => e,
+    }
+}
+
+// The copies of a track share its score, only the playing state is cloned.
+fn repeat(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 or more (track, times, endings)",
+                args.len()
+            ),
             line,
         );
     }
     let mut args = VecDeque::from(args);
-    let speed_el = match args.pop_front() {
-        Some(a) => a,
-        None => {
+    let ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+
+    let times_el = args.pop_front().unwrap();
+    let times_info = times_el.inspect();
+    let times = match times_el.get_type() {
+        Type::Int(i) if i >= 1 => i as usize,
+        _ => {
             return new_error(
-                "expecting at least 1 (note, sound, track), speed and depth for vibrato"
-                    .to_string(),
+                format!(
+                    "invalid repeat: expected the times as a positive integer, got {}",
+                    times_info
+                ),
                 line,
             )
         }
     };
-    let depth_el = match args.pop_front() {
-        Some(a) => a,
-        None => {
+    if args.len() > times {
+        return new_error(
+            format!(
+                "invalid repeat: {} endings given for {} times",
+                args.len(),
+                times
+            ),
+            line,
+        );
+    }
+    let endings = match args
+        .into_iter()
+        .map(|a| to_instrument(a, line))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(e) => e,
+        Err(e) => return e,
+    };
+
+    let mut parts = Vec::with_capacity(times * 2);
+    for i in 0..times {
+        parts.push(ins.clone());
+        // the last time takes the last ending, the others
+        // take theirs in order and the one before last once they run out
+        let ending = match (i + 1 == times, endings.len()) {
+            (_, 0) => None,
+            (true, n) => endings.get(n - 1),
+            (false, n) => endings.get(i.min(n.saturating_sub(2))),
+        };
+        if let Some(ending) = ending {
+            parts.push(ending.clone());
+        }
+    }
+    Box::new(Instrument::new(Box::new(Sequence::new(parts))))
+}
+
+fn at(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=2 (track, beats)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let ins = match to_instrument(args.pop_front().unwrap(), line) {
+        Ok(i) => i,
+        Err(e) => return e,
+    };
+
+    let delay_el = args.pop_front().unwrap();
+    let delay_info = delay_el.inspect();
+    let delay = match delay_el.get_type() {
+        // a beat is a quarter note
+        Type::Int(i) if i >= 0 => Duration::new(i as u32, 4),
+        Type::Duration(d) => d.get_dur(),
+        _ => {
             return new_error(
-                "expecting at least 1 (note, sound, track), speed and depth for vibrato"
-                    .to_string(),
+                format!(
+                    "invalid start: expected beats as a non-negative integer or a note value like d8, got {}",
+                    delay_info
+                ),
                 line,
             )
         }
     };
+    Box::new(Instrument::new(Box::new(Offset::new(ins, delay))))
+}
 
-    let speed_ins = speed_el.inspect();
-    let speed = match speed_el.get_type() {
-        Type::Int(i) => i,
-        _ => {
+// bar(bars[, beats per bar[, beat]]), 4 quarter note beats by default
+fn bar(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.is_empty() || args.len() > 3 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=1 to 3 (bars, beats per bar, beat)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+    let beat = match args.len() {
+        3 => {
+            let beat_el = args.pop_back().unwrap();
+            let beat_info = beat_el.inspect();
+            match beat_el.get_type() {
+                Type::Duration(d) => d.get_dur(),
+                _ => {
+                    return new_error(
+                        format!(
+                            "invalid beat: expected a note value like d8, got {}",
+                            beat_info
+                        ),
+                        line,
+                    )
+                }
+            }
+        }
+        _ => Duration::note(4),
+    };
+    let mut values = vec![];
+    for arg in args {
+        let info = arg.inspect();
+        match arg.get_type() {
+            Type::Int(i) if i >= 0 => values.push(i as u32),
+            _ => {
+                return new_error(
+                    format!("invalid bar: expected a non-negative integer, got {}", info),
+                    line,
+                )
+            }
+        }
+    }
+    let beats = values.get(1).copied().unwrap_or(4);
+    match values[0].checked_mul(beats).and_then(|n| beat.times(n)) {
+        Some(length) => Box::new(DurationObj::new(length)),
+        None => new_error(
+            format!(
+                "invalid bar: {} bars of {} beats are too long",
+                values[0], beats
+            ),
+            line,
+        ),
+    }
+}
+
+fn section(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    let mut args = VecDeque::from(args);
+    let first = match args.front() {
+        Some(f) => f.clone(),
+        None => {
             return new_error(
-                format!("invalid speed: expected integer, got {}", speed_ins),
+                "zero arguments given to section, expected tracks".to_string(),
                 line,
             )
         }
     };
-
-    let depth_ins = depth_el.inspect();
-    let depth = match depth_el.get_type() {
-        Type::Int(i) => i,
-        _ => {
+    let beat_per_min = match first.get_type() {
+        Type::Int(i) if i > 0 => {
+            args.pop_front();
+            Some(i as f32)
+        }
+        Type::Int(i) => {
             return new_error(
-                format!("invalid phase: expected integer, got {}", depth_ins),
+                format!("invalid section tempo: expected above 0, got {}", i),
                 line,
             )
         }
+        _ => None,
     };
-
-    let mut sounds = match notes_to_sounds(Vec::from(args), line) {
-        Ok(s) => s,
+    let parts = match to_instruments(Vec::from(args), "section", line) {
+        Ok(p) => p,
         Err(e) => return e,
     };
+    Box::new(Instrument::new(Box::new(Section::new(
+        Box::new(Stack::new(parts)),
+        beat_per_min,
+    ))))
+}
 
-    for chord in sounds.iter_mut() {
-        for mut sound in chord.iter_mut() {
-            let vib = Box::new(Vibrato::new(depth as f32, speed as f32));
-            match sound.effects.as_mut() {
-                Some(e_box) => e_box.push(vib),
-                None => sound.effects = Some(vec![vib]),
-            }
-        }
+// The form names the sections in order, separated by spaces
+// or as single letters when it has no spaces: "I V C V C O", "AABA".
+fn song(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() < 3 || args.len().is_multiple_of(2) {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=form followed by pairs of name and section",
+                args.len()
+            ),
+            line,
+        );
     }
+    let mut args = VecDeque::from(args);
 
-    let mut sound_chords = VecDeque::with_capacity(sounds.len());
+    let form_el = args.pop_front().unwrap();
+    let form_info = form_el.inspect();
+    let form = match form_el.get_type() {
+        Type::String(s) if !s.trim().is_empty() => s,
+        Type::String(_) => {
+            return new_error("invalid song form: no sections given".to_string(), line)
+        }
+        _ => {
+            return new_error(
+                format!("invalid song form: expected string, got {}", form_info),
+                line,
+            )
+        }
+    };
 
-    for sound in sounds {
-        let a: Vec<Sound> = sound.iter().map(|s| Sound::new(s.clone(), false)).collect();
-        sound_chords.push_back(Chord::new(a));
+    let mut sections: HashMap<String, InstrumentBox> = HashMap::new();
+    while let (Some(name_el), Some(section_el)) = (args.pop_front(), args.pop_front()) {
+        let name_info = name_el.inspect();
+        let name = match name_el.get_type() {
+            Type::String(s) if !s.is_empty() && !s.contains(char::is_whitespace) => s,
+            _ => {
+                return new_error(
+                    format!(
+                        "invalid section name: expected a string without spaces, got {}",
+                        name_info
+                    ),
+                    line,
+                )
+            }
+        };
+        let ins = match to_instrument(section_el, line) {
+            Ok(i) => i,
+            Err(e) => return e,
+        };
+        if sections.insert(name.clone(), ins).is_some() {
+            return new_error(format!("section `{}` is defined twice", name), line);
+        }
     }
 
-    Box::new(Sounds::new(sound_chords))
-}
-
-fn track(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
-    let ins = match notes_to_ins(args, line) {
-        Ok(s) => s,
-        Err(e) => return e,
+    // "AABA" spells the form one letter a section when every name is a letter,
+    // otherwise the names are separated by spaces
+    let form: Vec<String> = match form.contains(char::is_whitespace) {
+        true => form.split_whitespace().map(|n| n.to_string()).collect(),
+        false if sections.keys().all(|n| n.chars().count() == 1) => {
+            form.chars().map(|c| c.to_string()).collect()
+        }
+        false => vec![form],
     };
-    Box::new(Instrument::new(ins))
+
+    let mut parts = Vec::with_capacity(form.len());
+    for name in form {
+        match sections.get(&name) {
+            Some(ins) => parts.push(ins.clone()),
+            None => return new_error(format!("unknown section `{}` in the song form", name), line),
+        }
+    }
+    Box::new(Instrument::new(Box::new(Sequence::new(parts))))
 }
 
 fn play(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
@@ -157,6 +1915,154 @@ fn play(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
     Box::new(Null {})
 }
 
+// pitch(hz) tunes the a above middle c
+fn pitch(mut args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 1 {
+        return new_error(
+            format!("wrong number of arguments. got={}, want=1 (hz)", args.len()),
+            line,
+        );
+    }
+
+    let hz_el = args.remove(0);
+    let hz_info = hz_el.inspect();
+    let hz = match hz_el.get_type() {
+        Type::Int(i) if (100..=1000).contains(&i) => i,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid pitch: expected integer between 100 and 1000 Hz, got {}",
+                    hz_info
+                ),
+                line,
+            )
+        }
+    };
+
+    match TUNING.lock() {
+        Ok(mut t) => t.set_reference(hz as f32),
+        Err(_) => panic!("cannot get tuning"),
+    }
+    Box::new(Null {})
+}
+
+// tuning(name[, root]) or tuning("file.scl"[, "file.kbm"])
+fn tuning(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
+    if args.len() != 1 && args.len() != 2 {
+        return new_error(
+            format!(
+                "wrong number of arguments. got={}, want=1 or 2 (name, root) or (scl file, kbm file)",
+                args.len()
+            ),
+            line,
+        );
+    }
+    let mut args = VecDeque::from(args);
+
+    let name_el = args.pop_front().unwrap();
+    let name_info = name_el.inspect();
+    let name = match name_el.get_type() {
+        Type::String(s) => s,
+        _ => {
+            return new_error(
+                format!("invalid tuning: expected name or file, got {}", name_info),
+                line,
+            )
+        }
+    };
+
+    let has_kbm = name.ends_with(".scl") && args.len() == 1;
+    let mut new_tuning = match name.ends_with(".scl") {
+        true => {
+            let kbm_path = match args.pop_front() {
+                Some(kbm_el) => {
+                    let kbm_info = kbm_el.inspect();
+                    match kbm_el.get_type() {
+                        Type::String(s) => Some(s),
+                        _ => {
+                            return new_error(
+                                format!(
+                                    "invalid keyboard mapping: expected file, got {}",
+                                    kbm_info
+                                ),
+                                line,
+                            )
+                        }
+                    }
+                }
+                None => None,
+            };
+            // files are found next to the script
+            let dir = match CONFIG.lock() {
+                Ok(c) => c.dir.clone(),
+                Err(_) => panic!("cannot get config"),
+            };
+            let scl = match std::fs::read_to_string(dir.join(&name)) {
+                Ok(s) => s,
+                Err(e) => return new_error(format!("cannot read `{}`: {}", name, e), line),
+            };
+            let kbm = match kbm_path {
+                Some(path) => match std::fs::read_to_string(dir.join(&path)) {
+                    Ok(k) => Some(k),
+                    Err(e) => return new_error(format!("cannot read `{}`: {}", path, e), line),
+                },
+                None => None,
+            };
+            match Tuning::from_scala(&scl, kbm.as_deref()) {
+                Ok(t) => t,
+                Err(e) => return new_error(format!("invalid scala file `{}`: {}", name, e), line),
+            }
+        }
+        false => {
+            let root = match args.pop_front() {
+                Some(root_el) => {
+                    let root_info = root_el.inspect();
+                    let key = match root_el.get_type() {
+                        Type::Note(n) => Pitch::new(n.get_note(), Octave::Four),
+                        _ => None,
+                    };
+                    match key {
+                        Some(p) => p.key(),
+                        None => {
+                            return new_error(
+                                format!("invalid tuning root: expected note, got {}", root_info),
+                                line,
+                            )
+                        }
+                    }
+                }
+                None => Pitch::new(Note::new(Letter::C, 0), Octave::Four)
+                    .unwrap()
+                    .key(),
+            };
+            match Tuning::from_name(&name, root) {
+                Some(t) => t,
+                None => {
+                    return new_error(
+                        format!(
+                            "unknown tuning `{}`: expected \"equal\", \"just\", \"pythagorean\", \"meantone\", \"werckmeister\" or a .scl file",
+                            name
+                        ),
+                        line,
+                    )
+                }
+            }
+        }
+    };
+
+    match TUNING.lock() {
+        Ok(mut t) => {
+            // a keyboard mapping brings its own reference pitch
+            if !has_kbm {
+                new_tuning.reference_from(&t);
+            }
+            *t = new_tuning;
+        }
+        Err(_) => panic!("cannot get tuning"),
+    }
+    Box::new(Null {})
+}
+
 fn tempo(mut args: Vec<Box<dyn Object + 'static>>, line: usize) -> Box<dyn Object> {
     if args.len() != 1 {
         return new_error(
@@ -186,6 +2092,17 @@ fn new_opts() -> Options {
     Options {
         osc: Box::new(AnalogSaw::new()),
         env: Envelope::new(),
+        pan: 0.0,
+        gain: 1.0,
+        muted: false,
+        name: None,
+        chain: vec![],
+        filter: None,
+        glide: 0.0,
+        tuning: match TUNING.lock() {
+            Ok(t) => t.clone(),
+            Err(_) => panic!("cannot get tuning"),
+        },
     }
 }
 
@@ -203,13 +2120,17 @@ fn notes_to_sounds(
     let mut sounds = VecDeque::with_capacity(args.len());
     let mut i: usize = 0;
     let mut def_oct = Octave::One;
-    let mut def_dur = Duration::Whole;
+    let mut def_dur = Duration::note(1);
 
-    match args.first() {
+    // dynamic markings come before the notes they apply to
+    let first = args
+        .iter()
+        .find(|a| !matches!((*a).clone().get_type(), Type::Dynamic(_)));
+    match first {
         Some(first) => match first.clone().get_type() {
             Type::Sound(sound) => {
                 let s = sound.get_sound();
-                def_oct = s.octave.clone();
+                def_oct = s.octave();
                 def_dur = s.duration;
             }
             Type::Chord(chord) => {
@@ -221,7 +2142,7 @@ fn notes_to_sounds(
                         ));
                     }
                     let s = sound.clone().get_sound();
-                    def_oct = s.octave.clone();
+                    def_oct = s.octave();
                     def_dur = s.duration;
                 }
             }
@@ -241,21 +2162,24 @@ fn notes_to_sounds(
         }
     }
 
+    let mut velocity = None;
     for arg in args {
         let info = arg.inspect();
+        let first_new = sounds.len();
         match arg.get_type() {
+            Type::Dynamic(d) => velocity = Some(d.velocity),
             Type::Sound(sound) => {
                 let s = sound.clone().get_sound();
-                def_oct = s.octave.clone();
+                def_oct = s.octave();
                 def_dur = s.duration.clone();
                 sounds.push_back(vec![s]);
                 i += 1;
             }
-            Type::Note(n) => sounds.push_back(vec![PSound::new(
-                n.get_note(),
-                def_oct.clone(),
-                def_dur.clone(),
-            )]),
+            Type::Note(n) => {
+                let mut s = PSound::new(n.get_note(), def_oct.clone(), def_dur.clone());
+                s.velocity = n.velocity;
+                sounds.push_back(vec![s]);
+            }
             Type::Chord(chord) => {
                 let chord = chord.get_sounds();
                 let mut sound = Vec::with_capacity(chord.len());
@@ -274,6 +2198,18 @@ fn notes_to_sounds(
                     sounds.push_back(sound);
                 }
             }
+            // a scale plays its notes going up from the root
+            Type::Scale(scale) => {
+                match scale.get_scale().sounds(def_oct.clone(), def_dur.clone()) {
+                    Some(notes) => sounds.extend(notes.into_iter().map(|s| vec![s])),
+                    None => {
+                        return Err(new_error(
+                            format!("scale {} goes beyond the available octaves", info),
+                            line,
+                        ))
+                    }
+                }
+            }
             _ => {
                 return Err(new_error(
                     format!("expected note, argument {} is {}", i, info),
@@ -281,6 +2217,13 @@ fn notes_to_sounds(
                 ));
             }
         }
+
+        // notes with their own velocity ignore the marking
+        for chord in sounds.iter_mut().skip(first_new) {
+            for sound in chord.iter_mut() {
+                sound.velocity = sound.velocity.or(velocity);
+            }
+        }
     }
     Ok(sounds)
 }
@@ -305,6 +2248,7 @@ fn notes_to_song(
     let mut song = Song::new(*song_tempo);
 
     song.push_instrument(instr);
+    apply_mixer(&mut song);
     Ok(song)
 }
 
@@ -330,5 +2274,41 @@ fn ins_to_song(args: Vec<Box<dyn Object + 'static>>, line: usize) -> Result<Song
         ));
     }
 
+    apply_mixer(&mut song);
     Ok(song)
 }
+
+// Applies the script's master volume and the command line overrides.
+fn apply_mixer(song: &mut Song) {
+    let master = match MASTER.lock() {
+        Ok(m) => m,
+        Err(_) => panic!("cannot get master"),
+    };
+    let config = match CONFIG.lock() {
+        Ok(c) => c,
+        Err(_) => panic!("cannot get config"),
+    };
+
+    song.master = master.clone();
+    if let Some(db) = config.volume {
+        song.master.set_volume_db(db);
+    }
+    if let Some(db) = config.limit {
+        song.master.set_threshold_db(db);
+    }
+
+    for ins in song.instruments.iter_mut() {
+        ins.synths_mut(&mut |synth| {
+            let name = match synth.get_name() {
+                Some(n) => n.to_string(),
+                None => return,
+            };
+            if let Some(db) = config.gains.get(&name) {
+                synth.set_gain_db(*db);
+            }
+            if config.mutes.contains(&name) {
+                synth.set_muted(true);
+            }
+        });
+    }
+}
@@ -4,10 +4,14 @@ use crate::interpreter::ast::{
 };
 use crate::interpreter::builtin::BUILTINS;
 use crate::interpreter::object::{
-    Chord, CloneObj, Env, Error, IntObj, Note, Null, Object, Sound, Type,
+    Chord, CloneObj, Duration, Env, Error, Instrument, IntObj, Note, Null, Object, Sound,
+    StringObj, Type,
 };
 use crate::interpreter::token::{Token, TokenType};
+use crate::player::arrangement::Sequence;
+use crate::player::pitch::Pitch;
 use crate::player::sound::{Note as PNote, Sound as PSound};
+use crate::player::tempo::Duration as PDuration;
 
 pub fn eval(node: Box<dyn Node>, env: &mut Env) -> Box<dyn Object> {
     match node.get_type() {
@@ -20,16 +24,22 @@ pub fn eval(node: Box<dyn Node>, env: &mut Env) -> Box<dyn Object> {
             };
             int_obj.clone_obj()
         }
+        NodeType::StringLit(string_lit) => Box::new(StringObj {
+            value: string_lit.value,
+        }),
         NodeType::PrefixExpr(prefix_expr) => eval_prefix_expr(*prefix_expr, env),
         NodeType::AssignStmt(assign_statement) => eval_assign_statement(*assign_statement, env),
     }
 }
 
 fn eval_assign_statement(assign_statement: AssignStatement, env: &mut Env) -> Box<dyn Object> {
-    let expr = eval(assign_statement.value.to_node(), env);
+    let mut expr = eval(assign_statement.value.to_node(), env);
     if expr.is_error() {
         return expr;
     }
+    if let Type::Instrument(ins) = expr.clone().get_type() {
+        expr = Box::new(ins.named(&assign_statement.name.value));
+    }
     env.set(assign_statement.name.value, expr);
     Box::new(Null {})
 }
@@ -69,7 +79,7 @@ fn eval_call_exp(call_exp: CallExpression, env: &mut Env) -> Box<dyn Object> {
     }
 
     let args = eval_exprs(call_exp.args, env);
-    if let Some(arg) = args.get(0) {
+    if let Some(arg) = args.first() {
         if arg.is_error() {
             return arg.clone_obj();
         }
@@ -145,6 +155,10 @@ fn eval_infix_expr(infix_exp: InfixExpression, env: &mut Env) -> Box<dyn Object>
         (Type::Chord(l), Type::Note(r)) => {
             eval_note_infix_expr(&infix_exp.operator, l, r, infix_exp.token.line)
         }
+        // intro + verse
+        (Type::Instrument(l), Type::Instrument(r)) => {
+            eval_track_infix_expr(&infix_exp.operator, l, r, infix_exp.token.line)
+        }
         _ => new_error(
             format!(
                 "unknown operands for plus: left - {:?}  right - {:?}",
@@ -164,7 +178,7 @@ fn eval_note_infix_expr(op: &str, left: Chord, right: Note, line: usize) -> Box<
             let mut dur = None;
             for s in sounds.iter().rev() {
                 if !s.modified {
-                    oct = Some(s.sound.octave.clone());
+                    oct = Some(s.sound.octave());
                     dur = Some(s.sound.duration.clone());
                     break;
                 }
@@ -182,7 +196,19 @@ fn eval_note_infix_expr(op: &str, left: Chord, right: Note, line: usize) -> Box<
                     )
                 }
             };
-            sounds.push(Sound::new(PSound::new(right.get_note(), o, d), true));
+            if right.get_note() != PNote::Space && Pitch::new(right.get_note(), o.clone()).is_none()
+            {
+                return new_error(
+                    format!(
+                        "note `{}` is outside of the available octaves",
+                        right.get_note().name()
+                    ),
+                    line,
+                );
+            }
+            let mut sound = PSound::new(right.get_note(), o, d);
+            sound.velocity = right.velocity;
+            sounds.push(Sound::new(sound, true));
             Chord::new(sounds)
         }
         _ => {
@@ -237,6 +263,24 @@ fn eval_sound_infix_expr(op: &str, left: Sound, right: Sound, line: usize) -> Bo
     obj
 }
 
+fn eval_track_infix_expr(
+    op: &str,
+    left: Instrument,
+    right: Instrument,
+    line: usize,
+) -> Box<dyn Object> {
+    match op {
+        "+" => Box::new(Instrument::new(Box::new(Sequence::new(vec![
+            left.get_instrument(),
+            right.get_instrument(),
+        ])))),
+        _ => new_error(
+            format!("unknown operator: op: '{}' between tracks", op),
+            line,
+        ),
+    }
+}
+
 fn eval_int_infix_expr(op: &str, left: i32, right: i32, line: usize) -> Box<dyn Object> {
     let int = match op {
         "+" => left + right,
@@ -324,6 +368,13 @@ fn eval_note_ident(ident: Identifier, env: &Env) -> Box<dyn Object> {
         _ => return Box::new(n),
     };
 
+    if n.get_note() != PNote::Space && Pitch::new(n.get_note(), oct.get_oct()).is_none() {
+        return new_error(
+            format!("note `{}` is outside of the available octaves", ident_val),
+            ident.token.line,
+        );
+    }
+
     let dur = match spl.next() {
         Some(d) => {
             let dur_eval = eval_ident(
@@ -352,16 +403,58 @@ fn eval_note_ident(ident: Identifier, env: &Env) -> Box<dyn Object> {
     };
 
     Box::new(Sound {
-        sound: PSound {
-            note: n.get_note(),
-            octave: oct.get_oct(),
-            duration: dur.get_dur(),
-            effects: None,
-        },
+        sound: PSound::new(n.get_note(), oct.get_oct(), dur.get_dur()),
         modified: false,
     })
 }
 
+// c_4_8!90 or e!90
+fn eval_velocity_ident(ident: Identifier, env: &Env) -> Box<dyn Object> {
+    let ident_val = ident.get_value();
+    let (name, velocity) = ident_val.split_at(ident_val.find('!').unwrap());
+
+    let velocity = match velocity[1..].parse::<u8>() {
+        Ok(v) if (1..=127).contains(&v) => v,
+        _ => {
+            return new_error(
+                format!(
+                    "invalid velocity in `{}`: expected integer between 1 and 127",
+                    ident_val
+                ),
+                ident.token.line,
+            )
+        }
+    };
+
+    let obj = eval_ident(
+        Identifier {
+            token: Token {
+                ttype: TokenType::Ident,
+                literal: name.to_string(),
+                line: ident.token.line,
+            },
+            value: name.to_string(),
+        },
+        env,
+    );
+    let info = obj.inspect();
+    match obj.get_type() {
+        Type::Sound(mut s) => {
+            s.sound.velocity = Some(velocity);
+            Box::new(s)
+        }
+        Type::Note(mut n) => {
+            n.velocity = Some(velocity);
+            Box::new(n)
+        }
+        Type::Error(e) => Box::new(e),
+        _ => new_error(
+            format!("velocity can only be given to notes, got {}", info),
+            ident.token.line,
+        ),
+    }
+}
+
 fn eval_ident(ident: Identifier, env: &Env) -> Box<dyn Object> {
     if let Some(val) = env.get(ident.get_value().as_str()) {
         return val.clone_obj();
@@ -371,10 +464,20 @@ fn eval_ident(ident: Identifier, env: &Env) -> Box<dyn Object> {
         return builtin.clone_obj();
     }
 
+    if ident.get_value().contains('!') {
+        return eval_velocity_ident(ident, env);
+    }
+
     if ident.get_value().contains('_') {
         return eval_note_ident(ident, env);
     }
 
+    if let Some(name) = ident.get_value().strip_prefix('d') {
+        if let Some(d) = PDuration::from_name(name) {
+            return Box::new(Duration::new(d));
+        }
+    }
+
     new_error(
         format!("not found: `{}`", ident.to_string()),
         ident.token.line,
@@ -430,13 +533,16 @@ fn test_eval_float_not_implemented_expr() {
 }
 
 #[cfg(test)]
-use crate::interpreter::object::{Duration, Octave};
+use crate::interpreter::builtin::{CONFIG, TUNING};
 #[cfg(test)]
-use crate::player::sound::Octave as POctave;
+use crate::interpreter::object::{Dynamic, Octave};
 #[cfg(test)]
-use crate::player::tempo::Duration as PDuration;
+use crate::player::sound::{Letter, Octave as POctave};
+#[cfg(test)]
+use crate::player::tuning::Tuning;
 
 #[test]
+#[allow(clippy::never_loop)]
 fn test_track() {
     let expr = "let a = track(a_3_8*);";
     let lex = Lexer::new(expr);
@@ -444,11 +550,14 @@ fn test_track() {
     let program = p.parse_program();
     let mut env = Env::new();
 
-    env.set("a".to_string(), Box::new(Note::new(PNote::A)));
+    env.set(
+        "a".to_string(),
+        Box::new(Note::new(PNote::new(Letter::A, 0))),
+    );
     env.set("o3".to_string(), Box::new(Octave::new(POctave::Three)));
     env.set(
         "d8*".to_string(),
-        Box::new(Duration::new(PDuration::EightDotted)),
+        Box::new(Duration::new(PDuration::note(8).dotted(1))),
     );
 
     assert_eq!(1, program.exprs.len());
@@ -496,3 +605,506 @@ fn test_daniel_expr() {
         _ => panic!("expected Int, got {:?}", t),
     }
 }
+
+#[cfg(test)]
+fn new_test_env() -> Env {
+    let mut env = Env::new();
+
+    env.set(
+        "a".to_string(),
+        Box::new(Note::new(PNote::new(Letter::A, 0))),
+    );
+    env.set("o3".to_string(), Box::new(Octave::new(POctave::Three)));
+    env.set(
+        "d8*".to_string(),
+        Box::new(Duration::new(PDuration::note(8).dotted(1))),
+    );
+    env
+}
+
+#[test]
+fn test_track_builtins() {
+    let tests = vec![
+        ("pan(track(a_3_8*), -30);", false),
+        ("track(a_3_8*) + track(a_3_8*);", false),
+        ("track(a_3_8*) - track(a_3_8*);", true),
+        ("track(a_3_8*) + a_3_8*;", true),
+        ("seq(track(a_3_8*), track(a_3_8*), track(a_3_8*));", false),
+        ("seq(track(a_3_8*), a_3_8*);", true),
+        ("seq();", true),
+        ("stack(track(a_3_8*), seq(track(a_3_8*), track(a_3_8*)));", false),
+        ("stack(5);", true),
+        ("fx(seq(track(a_3_8*), track(a_3_8*)), reverb(80, 50, 30));", false),
+        ("repeat(track(a_3_8*), 4);", false),
+        ("repeat(track(a_3_8*), 2, track(a_3_8*), track(a_3_8*));", false),
+        ("repeat(track(a_3_8*), 1, track(a_3_8*), track(a_3_8*));", true),
+        ("repeat(track(a_3_8*), 0);", true),
+        ("repeat(track(a_3_8*));", true),
+        ("repeat(a_3_8*, 2);", true),
+        ("at(track(a_3_8*), 4);", false),
+        ("at(track(a_3_8*), d8*);", false),
+        ("at(track(a_3_8*), bar(2));", false),
+        ("at(track(a_3_8*), bar(2, 3));", false),
+        ("at(track(a_3_8*), bar(2, 6, d8));", false),
+        ("bar(2, 6, 8);", true),
+        ("bar(2, 6, d8, d8);", true),
+        ("at(track(a_3_8*), -1);", true),
+        ("at(a_3_8*, 1);", true),
+        ("bar();", true),
+        ("bar(d8);", true),
+        ("bar(100000, 100000);", true),
+        ("section(track(a_3_8*), track(a_3_8*));", false),
+        ("section(90, track(a_3_8*));", false),
+        ("section(0, track(a_3_8*));", true),
+        ("section(90);", true),
+        ("song(\"AABA\", \"A\", track(a_3_8*), \"B\", section(90, track(a_3_8*)));", false),
+        ("song(\"I V V\", \"I\", track(a_3_8*), \"V\", track(a_3_8*));", false),
+        ("song(\"I V\", \"I\", track(a_3_8*));", true),
+        ("song(\"AB\", \"A\", track(a_3_8*), \"A\", track(a_3_8*));", true),
+        ("song(\"Intro\", \"Intro\", track(a_3_8*));", false),
+        ("song(\"AB\", \"A\", track(a_3_8*), \"Bridge\", track(a_3_8*));", true),
+        ("song(\"\", \"A\", track(a_3_8*));", true),
+        ("song(\"A\", \"A\", a_3_8*);", true),
+        ("song(\"A\", \"A\");", true),
+        ("pan(track(a_3_8*) + track(a_3_8*), 20);", false),
+        ("pan(track(a_3_8*), 100);", false),
+        ("pan(track(a_3_8*), 101);", true),
+        ("pan(a_3_8*, 10);", true),
+        ("fx(track(a_3_8*), drive(50), drive(10));", false),
+        ("fx(track(a_3_8*));", true),
+        ("fx(track(a_3_8*), 5);", true),
+        ("drive(101);", true),
+        ("fx(track(a_3_8*), reverb(80, 50, 30));", false),
+        ("reverb(80, 50);", true),
+        ("master(reverb(80, 50, 30));", false),
+        ("master(a_3_8*);", true),
+        ("fx(track(a_3_8*), delay(d8*, 40, 30));", false),
+        ("fx(track(a_3_8*), delay(d8*, 40, 30, 60, 1));", false),
+        ("delay(40, 30, 60);", true),
+        ("filter(track(a_3_8*), \"lp\", 800, 60);", false),
+        (
+            "filter(track(a_3_8*), \"bp\", 800, 60, 24, 5, 200, 30, 50);",
+            false,
+        ),
+        ("filter(track(a_3_8*), \"notch\", 800, 60);", true),
+        ("filter(track(a_3_8*), \"hp\", 800, 120);", true),
+        (
+            "filter(track(a_3_8*), \"lp\", 800, 60, 24, 5, 200, 300, 50);",
+            true,
+        ),
+        (
+            "filter(track(a_3_8*), \"bp\", 400, 20, 24, 50, 200, 40, 80, 60);",
+            false,
+        ),
+        (
+            "filter(track(a_3_8*), \"bp\", 400, 20, 24, 50, 200, 40, 80, 160);",
+            true,
+        ),
+        ("vib(10, 5, track(a_3_8*));", false),
+        ("trem(d8*, 50, a_3_8*, a);", false),
+        ("trem(d8*, 50, track(a_3_8*));", false),
+        ("trem(10, 50, a_3_8*);", true),
+        ("am(30, 50, a_3_8* + a);", false),
+        ("ring(440, 100, track(a_3_8*));", false),
+        ("ring(440, 100, track(a_3_8*), a_3_8*);", true),
+        ("ring(0, 100, a_3_8*);", true),
+        ("track(a_3_8*, legato(a_3_8*, a));", false),
+        ("legato(a, a);", true),
+        ("glide(track(a_3_8*, a), 80);", false),
+        ("glide(track(a_3_8*, a), -80);", true),
+        ("track(bend(a_3_8*, 2), a);", false),
+        ("bend(a_3_8* + a, 1, \"exp\");", false),
+        ("bend(a_3_8*, 2, \"sharp\");", true),
+        ("bend(a_3_8*);", true),
+        (
+            "track(slide(a_3_8*, a), hammer(a_3_8*, a, a), pull(a_3_8*, a));",
+            false,
+        ),
+        ("track(arp(a_3_8* + a, \"updown\", d8*, 2));", false),
+        ("arp(a_3_8* + a, \"random\", d8*);", false),
+        ("arp(a_3_8* + a, \"sideways\", d8*);", true),
+        ("arp(a_3_8* + a, \"up\", d8*, 8);", true),
+        ("arp(a_3_8*, \"up\", d8*);", true),
+        ("transpose(a_3_8*, 14);", false),
+        ("transpose(a_3_8* + a, -5);", false),
+        ("transpose(legato(a_3_8*, a), 7);", false),
+        ("transpose(track(a_3_8*, a), -12);", false),
+        ("transpose(track(a_3_8*, a), 90);", true),
+        ("transpose(scale(a, \"major\"), 90);", true),
+        ("transpose(a_3_8*, -50);", true),
+        ("transpose(a, 2);", true),
+        ("track(maj(a_3_8*), min7(a_3_8*, 3), a);", false),
+        ("arp(sus4(a_3_8*, 1), \"up\", d8*);", false),
+        ("dom7(a_3_8*, 4);", true),
+        ("dim(a);", true),
+        ("degree(scale(a, \"dorian\"), 3, o3, d8*);", false),
+        (
+            "let s = scale(a_3_8*, 2, 2, 3, 2, 3); track(degree(s, 1, o3, d8*), degree(s, 9, o3, d8*));",
+            false,
+        ),
+        ("scale(a, \"klingon\");", true),
+        ("scale(a, 7, 7);", true),
+        ("degree(scale(a, \"major\"), 0, o3, d8*);", true),
+        ("degree(scale(a, \"major\"), 80, o3, d8*);", true),
+        ("degree(a, 1, o3, d8*);", true),
+        ("track(a_3_8t, a, a, a_3_4~8, a_3_4**, a_3_16q);", false),
+        ("arp(a_3_2 + a, \"up\", d8t);", false),
+        ("track(a_3_3);", true),
+        ("track(a_3_4~);", true),
+        ("track(a_3_8*!90, a!20, a_3_8*);", false),
+        ("a_3_8*!0;", true),
+        ("a!128;", true),
+        ("track!5;", true),
+        ("track(cresc(20, 100, a_3_8*, a, a), a);", false),
+        ("decresc(110, 30, \"exp\", a_3_8* + a, a);", false),
+        ("dim(110, 30, a_3_8*);", false),
+        ("dim(pp, ff, a_3_8*);", true),
+        ("cresc(100, 20, a_3_8*);", true),
+        ("decresc(20, 100, a_3_8*);", true),
+        ("cresc(20, 100, \"sharp\", a_3_8*);", true),
+        ("cresc(0, 100, a_3_8*);", true),
+        ("track(stac(a_3_8*, a), ten(a_3_8*), acc(a_3_8* + a), ferm(a_3_8*));", false),
+        ("stac(track(a_3_8*, a));", false),
+        ("acc(track(a_3_8*), a);", true),
+        ("ferm(5);", true),
+        ("pitch(44);", true),
+        ("tuning(\"just\", a_3_8*);", true),
+        ("tuning(\"bohlen-pierce\");", true),
+        ("tuning(\"missing.scl\");", true),
+    ];
+
+    for (expr, is_err) in tests {
+        let lex = Lexer::new(expr);
+        let mut p = Parser::new(lex);
+        let program = p.parse_program();
+        let mut env = new_test_env();
+
+        for exp in program.exprs {
+            let obj = eval(exp.to_node(), &mut env);
+            assert_eq!(is_err, obj.is_error(), "{}: {}", expr, obj.inspect());
+        }
+    }
+}
+
+#[test]
+fn test_tuning_builtins() {
+    let dir = std::env::temp_dir().join(format!("soundbytes-tuning-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let scl = "! slendro.scl\n!\nFive note slendro\n 5\n!\n 240.0\n 480.0\n 720.0\n 960.0\n 2/1\n";
+    std::fs::write(dir.join("slendro.scl"), scl).unwrap();
+    CONFIG.lock().unwrap().dir = dir.clone();
+
+    let expr = r#"pitch(432); tuning("just"); tuning("slendro.scl");"#;
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let mut env = new_test_env();
+
+    let mut frequencies = vec![];
+    for exp in program.exprs {
+        let obj = eval(exp.to_node(), &mut env);
+        assert!(!obj.is_error(), "{}", obj.inspect());
+        let tuning = TUNING.lock().unwrap();
+        frequencies.push((
+            tuning.frequency(69),
+            tuning.frequency(60),
+            tuning.frequency(61),
+        ));
+    }
+
+    // the tuning is shared by every track, put it back before checking
+    *TUNING.lock().unwrap() = Tuning::equal();
+    CONFIG.lock().unwrap().dir = Default::default();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let close = |expected: f32, actual: f32| (expected - actual).abs() < 0.01;
+    let (a, _, _) = frequencies[0];
+    assert!(close(432.0, a), "pitch: {}", a);
+    // the sixth above c is 5/3 and a keeps its pitch
+    let (a, c, _) = frequencies[1];
+    assert!(close(432.0, a) && close(259.2, c), "just: {} {}", a, c);
+    // the scala file is read next to the script
+    let (a, c, c_sharp) = frequencies[2];
+    assert!(close(432.0, a), "slendro: {}", a);
+    assert!(
+        close(c * 2.0_f32.powf(0.2), c_sharp),
+        "slendro: {} {}",
+        c,
+        c_sharp
+    );
+}
+
+#[test]
+fn test_assign_names_track() {
+    let expr = "let bass = track(a_3_8*); let quiet = gain(bass, -6);";
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let mut env = new_test_env();
+
+    for exp in program.exprs {
+        let obj = eval(exp.to_node(), &mut env);
+        assert!(!obj.is_error(), "{}", obj.inspect());
+    }
+
+    for var in &["bass", "quiet"] {
+        let mut ins = match env.get(var).unwrap().get_type() {
+            Type::Instrument(ins) => ins.get_instrument(),
+            t => panic!("expected Instrument, got {:?}", t),
+        };
+        ins.synths_mut(&mut |synth| assert_eq!(Some("bass"), synth.get_name()));
+    }
+}
+
+#[test]
+fn test_arp_fills_chord() {
+    let expr = "arp(a_3_1 + c + e, \"updown\", d16, 2);";
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let mut env = new_test_env();
+    env.set(
+        "c".to_string(),
+        Box::new(Note::new(PNote::new(Letter::C, 0))),
+    );
+    env.set(
+        "e".to_string(),
+        Box::new(Note::new(PNote::new(Letter::E, 0))),
+    );
+    env.set(
+        "d1".to_string(),
+        Box::new(Duration::new(PDuration::note(1))),
+    );
+    env.set(
+        "d16".to_string(),
+        Box::new(Duration::new(PDuration::note(16))),
+    );
+
+    let obj = eval(program.exprs[0].to_node(), &mut env);
+    let sounds = match obj.get_type() {
+        Type::Sounds(s) => s.get_sounds(),
+        t => panic!("expected Sounds, got {:?}", t),
+    };
+    assert_eq!(16, sounds.len());
+
+    // c e a c e a, then back down without repeating the ends
+    let octaves: Vec<POctave> = sounds
+        .into_iter()
+        .map(|chord| chord.get_sounds()[0].sound.octave())
+        .collect();
+    for (i, oct) in octaves.iter().enumerate() {
+        let expected = match i % 10 {
+            0..=2 | 8 | 9 => 3,
+            _ => 4,
+        };
+        assert_eq!(expected, oct.number(), "step {}", i);
+    }
+}
+
+#[cfg(test)]
+fn eval_pitches(expr: &str, env: &mut Env) -> Vec<(PNote, i32)> {
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let obj = eval(program.exprs[0].to_node(), env);
+
+    let pitch = |s: &PSound| (s.note(), s.octave().number());
+    match obj.get_type() {
+        Type::Sound(s) => vec![pitch(&s.sound)],
+        Type::Chord(c) => c.get_sounds().iter().map(|s| pitch(&s.sound)).collect(),
+        Type::Instrument(ins) => {
+            let mut pitches = vec![];
+            ins.get_instrument()
+                .synths_mut(&mut |synth| pitches.extend(synth.sounds().map(pitch)));
+            pitches
+        }
+        t => panic!("expected notes, got {:?}", t),
+    }
+}
+
+#[test]
+fn test_transpose_rolls_over_octaves() {
+    let a = PNote::new(Letter::A, 0);
+    let b = PNote::new(Letter::B, 0);
+    let c = PNote::new(Letter::C, 0);
+    let tests = vec![
+        ("transpose(a_3_8*, 3);", vec![(c, 4)]),
+        ("transpose(a_3_8*, -10);", vec![(b, 2)]),
+        ("transpose(a_3_8* + a, 12);", vec![(a, 4), (a, 4)]),
+        ("transpose(track(a_3_8*, a), 15);", vec![(c, 5), (c, 5)]),
+    ];
+
+    for (expr, expected) in tests {
+        let mut env = new_test_env();
+        assert_eq!(expected, eval_pitches(expr, &mut env), "{}", expr);
+    }
+}
+
+#[test]
+fn test_named_chord_intervals() {
+    let tests = vec![
+        ("maj(a_3_8*);", vec![0, 4, 7]),
+        ("min(a_3_8*);", vec![0, 3, 7]),
+        ("dim(a_3_8*);", vec![0, 3, 6]),
+        ("aug(a_3_8*);", vec![0, 4, 8]),
+        ("sus2(a_3_8*);", vec![0, 2, 7]),
+        ("sus4(a_3_8*);", vec![0, 5, 7]),
+        ("maj7(a_3_8*);", vec![0, 4, 7, 11]),
+        ("min7(a_3_8*);", vec![0, 3, 7, 10]),
+        ("dom7(a_3_8*);", vec![0, 4, 7, 10]),
+        ("dim7(a_3_8*);", vec![0, 3, 6, 9]),
+        ("add9(a_3_8*);", vec![0, 4, 7, 14]),
+        ("maj(a_3_8*, 2);", vec![7, 12, 16]),
+        ("dom7(a_3_8*, 3);", vec![10, 12, 16, 19]),
+    ];
+
+    for (expr, expected) in tests {
+        let mut env = new_test_env();
+        // semitones above the a the chords are built on
+        let intervals: Vec<i32> = eval_pitches(expr, &mut env)
+            .into_iter()
+            .map(|(note, octave)| (octave + 1) * 12 + note.class().unwrap() - 57)
+            .collect();
+        assert_eq!(expected, intervals, "{}", expr);
+    }
+}
+
+#[test]
+fn test_scales_play_as_notes() {
+    let tests = vec![
+        (
+            r#"track(a_3_8*, scale(a, "minor pentatonic"));"#,
+            vec![0, 0, 3, 5, 7, 10],
+        ),
+        (
+            r#"track(a_3_8*, transpose(scale(a, "minor pentatonic"), 2));"#,
+            vec![0, 2, 5, 7, 9, 12],
+        ),
+        (
+            r#"track(a_3_8*, transpose(scale(a, "major"), 3));"#,
+            vec![0, 3, 5, 7, 8, 10, 12, 14],
+        ),
+        (
+            r#"track(a_3_8*, transpose(scale(a, "major"), 12));"#,
+            vec![0, 12, 14, 16, 17, 19, 21, 23],
+        ),
+    ];
+
+    for (expr, expected) in tests {
+        let mut env = new_test_env();
+        let intervals: Vec<i32> = eval_pitches(expr, &mut env)
+            .into_iter()
+            .map(|(note, octave)| (octave + 1) * 12 + note.class().unwrap() - 57)
+            .collect();
+        assert_eq!(expected, intervals, "{}", expr);
+    }
+}
+
+#[test]
+fn test_named_chord_inversion() {
+    let expr = "maj(a_3_8*, 1);";
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let mut env = new_test_env();
+
+    let obj = eval(program.exprs[0].to_node(), &mut env);
+    let sounds = match obj.get_type() {
+        Type::Chord(c) => c.get_sounds(),
+        t => panic!("expected Chord, got {:?}", t),
+    };
+
+    // first inversion of a major: c# e a
+    let notes: Vec<(PNote, i32, bool)> = sounds
+        .into_iter()
+        .map(|s| (s.sound.note(), s.sound.octave().number(), s.modified))
+        .collect();
+    assert_eq!((PNote::new(Letter::C, 1), 4, false), notes[0]);
+    assert_eq!((PNote::new(Letter::E, 0), 4, true), notes[1]);
+    assert_eq!((PNote::new(Letter::A, 0), 4, true), notes[2]);
+}
+
+#[test]
+fn test_flat_note_identifiers() {
+    let mut env = new_test_env();
+    env.set(
+        "cb".to_string(),
+        Box::new(Note::new(PNote::new(Letter::C, -1))),
+    );
+    env.set("o0".to_string(), Box::new(Octave::new(POctave::Zero)));
+    env.set("o9".to_string(), Box::new(Octave::new(POctave::Nine)));
+
+    let tests = vec![
+        ("cb_3_8*;", Some((PNote::new(Letter::C, -1), 3))),
+        ("a_0_8*;", Some((PNote::new(Letter::A, 0), 0))),
+        ("cb_0_8*;", None),
+        ("a_9_8*;", None),
+    ];
+
+    for (expr, expected) in tests {
+        let lex = Lexer::new(expr);
+        let mut p = Parser::new(lex);
+        let program = p.parse_program();
+        let obj = eval(program.exprs[0].to_node(), &mut env);
+        match (obj.get_type(), expected) {
+            (Type::Sound(s), Some((note, octave))) => {
+                assert_eq!(note, s.sound.note(), "{}", expr);
+                assert_eq!(octave, s.sound.octave().number(), "{}", expr);
+            }
+            (Type::Error(_), None) => {}
+            (t, _) => panic!("{}: unexpected {:?}", expr, t),
+        }
+    }
+}
+
+#[test]
+fn test_dynamics_apply_to_following_notes() {
+    let expr = "legato(pp, a_3_8*, a!100, a_3_8* + a, ff, a);";
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let mut env = new_test_env();
+    env.set("pp".to_string(), Box::new(Dynamic { velocity: 33 }));
+    env.set("ff".to_string(), Box::new(Dynamic { velocity: 112 }));
+
+    let obj = eval(program.exprs[0].to_node(), &mut env);
+    let sounds = match obj.get_type() {
+        Type::Sounds(s) => s.get_sounds(),
+        t => panic!("expected Sounds, got {:?}", t),
+    };
+    let velocities: Vec<Vec<Option<u8>>> = sounds
+        .into_iter()
+        .map(|c| c.get_sounds().iter().map(|s| s.sound.velocity).collect())
+        .collect();
+    assert_eq!(
+        vec![
+            vec![Some(33)],
+            vec![Some(100)],
+            vec![Some(33), Some(33)],
+            vec![Some(112)],
+        ],
+        velocities
+    );
+}
+
+#[test]
+fn test_crescendo_follows_durations() {
+    let expr = "cresc(pp, 93, a_3_2, a_3_4, a, a);";
+    let lex = Lexer::new(expr);
+    let mut p = Parser::new(lex);
+    let program = p.parse_program();
+    let mut env = new_test_env();
+    env.set("pp".to_string(), Box::new(Dynamic { velocity: 33 }));
+
+    let obj = eval(program.exprs[0].to_node(), &mut env);
+    let sounds = match obj.get_type() {
+        Type::Sounds(s) => s.get_sounds(),
+        t => panic!("expected Sounds, got {:?}", t),
+    };
+    let velocities: Vec<Option<u8>> = sounds
+        .into_iter()
+        .map(|c| c.get_sounds()[0].sound.velocity)
+        .collect();
+    // the notes start at beats 0, 2, 3 and 4
+    assert_eq!(vec![Some(33), Some(63), Some(78), Some(93)], velocities);
+}
@@ -28,19 +28,22 @@ impl Lexer {
         self.skip_all_whitespace();
         self.skip_comments();
         self.skip_all_whitespace();
-        let tok;
-
-        match self.ch {
-            '+' => tok = new_token(TokenType::Plus, self.ch, self.line),
-            '*' => tok = new_token(TokenType::Asterisk, self.ch, self.line),
-            '(' => tok = new_token(TokenType::Lparen, self.ch, self.line),
-            ')' => tok = new_token(TokenType::Rparen, self.ch, self.line),
-            ';' => tok = new_token(TokenType::Semicolon, self.ch, self.line),
-            '/' => tok = new_token(TokenType::Slash, self.ch, self.line),
-            '-' => tok = new_token(TokenType::Minus, self.ch, self.line),
-            ',' => tok = new_token(TokenType::Comma, self.ch, self.line),
-            '=' => tok = new_token(TokenType::Assign, self.ch, self.line),
-            DEFAULT_CHAR => tok = new_token(TokenType::Eof, DEFAULT_CHAR, self.line),
+        let tok = match self.ch {
+            '+' => new_token(TokenType::Plus, self.ch, self.line),
+            '*' => new_token(TokenType::Asterisk, self.ch, self.line),
+            '(' => new_token(TokenType::Lparen, self.ch, self.line),
+            ')' => new_token(TokenType::Rparen, self.ch, self.line),
+            ';' => new_token(TokenType::Semicolon, self.ch, self.line),
+            '/' => new_token(TokenType::Slash, self.ch, self.line),
+            '-' => new_token(TokenType::Minus, self.ch, self.line),
+            ',' => new_token(TokenType::Comma, self.ch, self.line),
+            '=' => new_token(TokenType::Assign, self.ch, self.line),
+            '"' => Token {
+                ttype: TokenType::String,
+                literal: self.read_string(),
+                line: self.line,
+            },
+            DEFAULT_CHAR => new_token(TokenType::Eof, DEFAULT_CHAR, self.line),
             _ => {
                 if self.ch.is_alphabetic() {
                     let literal = self.read_ident();
@@ -51,24 +54,23 @@ impl Lexer {
                         line: self.line,
                     };
                 }
-                if self.ch.is_digit(10) {
+                if self.ch.is_ascii_digit() {
                     return Token {
                         ttype: TokenType::Int,
                         literal: self.read_number(),
                         line: self.line,
                     };
                 }
-                tok = Token {
+                Token {
                     ttype: TokenType::Illegal,
                     literal: self.ch.to_string(),
                     line: self.line,
-                };
+                }
             }
-        }
+        };
         self.read_char();
         tok
     }
-    #[allow(dead_code)]
     fn read_string(&mut self) -> String {
         let position = self.position + 1;
 
@@ -85,7 +87,7 @@ impl Lexer {
     fn read_number(&mut self) -> String {
         let position = self.position;
 
-        while self.ch.is_digit(10) {
+        while self.ch.is_ascii_digit() {
             self.read_char()
         }
 
@@ -103,7 +105,12 @@ impl Lexer {
     }
 
     fn is_ident_char(&self) -> bool {
-        self.ch == '#' || self.ch == '*' || self.ch == '_' || self.ch.is_digit(10)
+        self.ch == '#'
+            || self.ch == '*'
+            || self.ch == '_'
+            || self.ch == '~'
+            || self.ch == '!'
+            || self.ch.is_ascii_digit()
     }
 
     fn peek_char(&mut self) -> char {
@@ -293,6 +300,31 @@ a_4_32);";
     assert_eq!(12, tok.line);
 }
 
+#[test]
+fn test_string() {
+    let input = "filter(t, \"lp\", 800);";
+    let tokens_type: [TokenType; 9] = [
+        TokenType::Ident,
+        TokenType::Lparen,
+        TokenType::Ident,
+        TokenType::Comma,
+        TokenType::String,
+        TokenType::Comma,
+        TokenType::Int,
+        TokenType::Rparen,
+        TokenType::Semicolon,
+    ];
+    let tokens_str: [&str; 9] = ["filter", "(", "t", ",", "lp", ",", "800", ")", ";"];
+
+    let mut lex = Lexer::new(input);
+
+    for (key, token) in tokens_type.iter().enumerate() {
+        let tok = lex.next_token();
+        assert_eq!(token, &tok.ttype);
+        assert_eq!(tokens_str[key], tok.literal);
+    }
+}
+
 #[test]
 fn test_assignment() {
     let input = "let foo = c_4_4;";
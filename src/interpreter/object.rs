@@ -1,4 +1,6 @@
+use crate::player::effect::ProcessorBox;
 use crate::player::instrument::InstrumentBox;
+use crate::player::scale::Scale as PScale;
 use crate::player::sound::{Note as PNote, Octave as POctave, Sound as PSound};
 use crate::player::tempo::Duration as PDuration;
 use std::collections::{HashMap, VecDeque};
@@ -15,6 +17,9 @@ pub enum Type {
     Sounds(Sounds),
     Chord(Chord),
     Instrument(Instrument),
+    Processor(Processor),
+    Scale(Scale),
+    Dynamic(Dynamic),
     Note(Note),
     Octave(Octave),
     Duration(Duration),
@@ -34,6 +39,9 @@ impl Debug for Type {
             Self::Sounds(sounds) => f.write_str(&format!("{:?}", sounds)),
             Self::Chord(c) => f.write_str(&format!("chord {:?}", c)),
             Self::Instrument(n) => f.write_str(&n.inspect()),
+            Self::Processor(n) => f.write_str(&n.inspect()),
+            Self::Scale(n) => f.write_str(&n.inspect()),
+            Self::Dynamic(n) => f.write_str(&n.inspect()),
             Self::Note(n) => f.write_str(&n.inspect()),
             Self::Octave(n) => f.write_str(&n.inspect()),
             Self::Duration(n) => f.write_str(&n.inspect()),
@@ -130,6 +138,10 @@ impl Instrument {
     pub fn get_instrument(&self) -> InstrumentBox {
         self.ins.clone()
     }
+    pub fn named(mut self, name: &str) -> Self {
+        self.ins.synths_mut(&mut |synth| synth.name_once(name));
+        self
+    }
 }
 
 impl Object for Instrument {
@@ -142,6 +154,70 @@ impl Object for Instrument {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Processor {
+    processor: ProcessorBox,
+}
+
+impl Processor {
+    pub fn new(processor: ProcessorBox) -> Self {
+        Self { processor }
+    }
+    pub fn get_processor(&self) -> ProcessorBox {
+        self.processor.clone()
+    }
+}
+
+impl Object for Processor {
+    fn get_type(self: Box<Self>) -> Type {
+        Type::Processor(*self)
+    }
+
+    fn inspect(&self) -> String {
+        format!("Processor: {:?}", self.processor)
+    }
+}
+
+/// A dynamic marking like `pp`, it sets the velocity of the notes after it.
+#[derive(Clone, Debug)]
+pub struct Dynamic {
+    pub(crate) velocity: u8,
+}
+
+impl Object for Dynamic {
+    fn get_type(self: Box<Self>) -> Type {
+        Type::Dynamic(*self)
+    }
+
+    fn inspect(&self) -> String {
+        format!("Dynamic: {}", self.velocity)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Scale {
+    scale: PScale,
+}
+
+impl Scale {
+    pub fn new(scale: PScale) -> Self {
+        Self { scale }
+    }
+    pub fn get_scale(&self) -> PScale {
+        self.scale.clone()
+    }
+}
+
+impl Object for Scale {
+    fn get_type(self: Box<Self>) -> Type {
+        Type::Scale(*self)
+    }
+
+    fn inspect(&self) -> String {
+        format!("Scale: {:?}", self.scale)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Duration {
     dur: PDuration,
@@ -193,14 +269,18 @@ impl Object for Octave {
 #[derive(Clone, Debug)]
 pub struct Note {
     note: PNote,
+    pub(crate) velocity: Option<u8>,
 }
 
 impl Note {
     pub fn new(note: PNote) -> Self {
-        Self { note }
+        Self {
+            note,
+            velocity: None,
+        }
     }
     pub fn get_note(&self) -> PNote {
-        self.note.clone()
+        self.note
     }
 }
 
@@ -210,7 +290,7 @@ impl Object for Note {
     }
 
     fn inspect(&self) -> String {
-        format!("Note: {:?}", self.note)
+        format!("Note: {}", self.note.name())
     }
 }
 
@@ -236,8 +316,10 @@ impl Object for Sound {
 
     fn inspect(&self) -> String {
         format!(
-            "note: {:?} duration: {:?} octave: {:?}",
-            self.sound.note, self.sound.duration, self.sound.octave
+            "note: {} duration: {:?} octave: {:?}",
+            self.sound.note().name(),
+            self.sound.duration,
+            self.sound.octave()
         )
     }
 }
@@ -286,6 +368,7 @@ impl Object for IntObj {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct FloatObj {
     pub(crate) value: f32,
@@ -316,6 +399,7 @@ impl Object for StringObj {
     }
 }
 
+#[allow(dead_code)]
 pub trait BuiltinFn: Fn(Vec<ObjectBox>) -> ObjectBox + Sync {}
 impl BuiltinFn for fn(Vec<ObjectBox>) -> ObjectBox {}
 type DefaultBuiltinFunc = fn(Vec<Box<dyn Object + 'static>>, line: usize) -> ObjectBox;
@@ -1,7 +1,7 @@
 use crate::interpreter::ast::AssignStatement;
 use crate::interpreter::ast::{
     CallExpression, Expression, Identifier, InfixExpression, IntegerLiteral, PrefixExpression,
-    Program,
+    Program, StringLiteral,
 };
 use crate::interpreter::lexer::Lexer;
 use crate::interpreter::token::{Token, TokenType};
@@ -30,6 +30,7 @@ lazy_static! {
     };
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum ParseErr {
     NoPrefix(Token),
@@ -178,6 +179,7 @@ impl Parser {
         match token_type {
             TokenType::Lparen => self.parse_grouped_expr(),
             TokenType::Int => self.parse_int_lit(),
+            TokenType::String => Some(self.parse_string_lit()),
             TokenType::Minus | TokenType::Asterisk => self.parse_prefix_expr(),
             TokenType::Ident => Some(self.parse_ident()),
             _ => unimplemented!("prefix {:?}", self.cur_token),
@@ -193,6 +195,15 @@ impl Parser {
         })
     }
 
+    fn parse_string_lit(&self) -> Box<dyn Expression> {
+        let tok = self.cur_token.clone();
+        let lit = tok.literal.to_string();
+        Box::new(StringLiteral {
+            token: tok,
+            value: lit,
+        })
+    }
+
     fn parse_prefix_expr(&mut self) -> Option<Box<dyn Expression>> {
         let cur_token = self.cur_token.clone();
         self.next_token();
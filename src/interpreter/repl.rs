@@ -1,12 +1,30 @@
+use crate::interpreter::builtin::CONFIG;
 use crate::interpreter::eval::eval;
 use crate::interpreter::lexer::Lexer;
-use crate::interpreter::object::{Duration, Env, Note, Object, Octave, Type};
+use crate::interpreter::object::{Dynamic, Env, Note, Object, Octave, Type};
 use crate::interpreter::parser::Parser;
-use crate::player::sound::{Note as PNote, Octave as POctave};
-use crate::player::tempo::Duration as PDUration;
+use crate::player::sound::{Letter, Note as PNote, Octave as POctave};
+use std::collections::{HashMap, HashSet};
 use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
 
-pub fn start(in_: impl Read, mut out: impl Write) {
+/// Mixer settings given on the command line.
+/// They take precedence over the ones set by the script.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub volume: Option<f32>,
+    pub limit: Option<f32>,
+    pub gains: HashMap<String, f32>,
+    pub mutes: HashSet<String>,
+    /// Directory of the script, the files it loads are relative to it.
+    pub dir: PathBuf,
+}
+
+pub fn start(in_: impl Read, mut out: impl Write, config: Config) {
+    match CONFIG.lock() {
+        Ok(mut c) => *c = config,
+        Err(_) => panic!("cannot set config"),
+    }
     let mut buf_reader = BufReader::new(in_);
     let mut env = Env::new();
     inject_predeclared(&mut env);
@@ -35,23 +53,41 @@ fn inject_predeclared(env: &mut Env) {
     };
 
     inject_note(PNote::Space, "x");
-    inject_note(PNote::A, "a");
-    inject_note(PNote::ASharp, "a#");
-    inject_note(PNote::B, "b");
-    inject_note(PNote::C, "c");
-    inject_note(PNote::CSharp, "c#");
-    inject_note(PNote::D, "d");
-    inject_note(PNote::DSharp, "d#");
-    inject_note(PNote::E, "e");
-    inject_note(PNote::F, "f");
-    inject_note(PNote::FSharp, "f#");
-    inject_note(PNote::G, "g");
-    inject_note(PNote::GSharp, "g#");
+    let letters = [
+        Letter::C,
+        Letter::D,
+        Letter::E,
+        Letter::F,
+        Letter::G,
+        Letter::A,
+        Letter::B,
+    ];
+    for letter in letters.iter() {
+        for accidentals in -2..=2 {
+            let note = PNote::new(*letter, accidentals);
+            inject_note(note, &note.name());
+        }
+    }
+
+    let mut inject_dynamic = |velocity: u8, k: &str| {
+        env.set(k.to_string(), Box::new(Dynamic { velocity }));
+    };
+
+    // `f` is the note, forte is spelled out
+    inject_dynamic(16, "ppp");
+    inject_dynamic(33, "pp");
+    inject_dynamic(49, "p");
+    inject_dynamic(64, "mp");
+    inject_dynamic(80, "mf");
+    inject_dynamic(96, "forte");
+    inject_dynamic(112, "ff");
+    inject_dynamic(127, "fff");
 
     let mut inject_octave = |n: POctave, k: &str| {
         env.set(k.to_string(), Box::new(Octave::new(n)));
     };
 
+    inject_octave(POctave::Zero, "o0");
     inject_octave(POctave::One, "o1");
     inject_octave(POctave::Two, "o2");
     inject_octave(POctave::Three, "o3");
@@ -60,24 +96,7 @@ fn inject_predeclared(env: &mut Env) {
     inject_octave(POctave::Six, "o6");
     inject_octave(POctave::Seven, "o7");
     inject_octave(POctave::Eight, "o8");
-
-    let mut inject_dur = |n: PDUration, k: &str| {
-        env.set(k.to_string(), Box::new(Duration::new(n)));
-    };
-
-    inject_dur(PDUration::Whole, "d1");
-    inject_dur(PDUration::HalfDotted, "d2*");
-    inject_dur(PDUration::Half, "d2");
-    inject_dur(PDUration::QuarterDotted, "d4*");
-    inject_dur(PDUration::Quarter, "d4");
-    inject_dur(PDUration::EightDotted, "d8*");
-    inject_dur(PDUration::Eight, "d8");
-    inject_dur(PDUration::SixteenthDotted, "d16*");
-    inject_dur(PDUration::Sixteenth, "d16");
-    inject_dur(PDUration::ThirtySecondDotted, "d32*");
-    inject_dur(PDUration::ThirtySecond, "d32");
-    inject_dur(PDUration::SixteenthDotted, "d64*");
-    inject_dur(PDUration::SixtyFourth, "d64");
+    inject_octave(POctave::Nine, "o9");
 }
 
 #[allow(dead_code)]
@@ -13,9 +13,10 @@ pub struct Token {
     pub line: usize,
 }
 
-#[derive(Clone, Debug, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Hash, Default)]
 #[allow(dead_code)]
 pub enum TokenType {
+    #[default]
     Illegal,
     Eof,
 
@@ -26,6 +27,7 @@ pub enum TokenType {
 
     // Literal
     Int,
+    String,
 
     // Operators
     Plus,
@@ -56,12 +58,6 @@ pub enum TokenType {
     Let,
 }
 
-impl Default for TokenType {
-    fn default() -> Self {
-        TokenType::Illegal
-    }
-}
-
 #[allow(dead_code)]
 impl TokenType {
     fn from_str(s: &str) -> Self {
@@ -9,6 +9,7 @@ extern crate log;
 extern crate relative_path;
 
 use interpreter::repl;
+use interpreter::repl::Config;
 use log::error;
 use relative_path::RelativePath;
 use std::env;
@@ -18,25 +19,76 @@ use std::io::stdout;
 fn main() {
     env::set_var("RUST_LOG", "soundbytes=warn");
     env_logger::init();
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let s = match args.get(1) {
-        Some(s) => s,
-        None => {
-            error!("no input given");
+    let (s, mut config) = match parse_args(args) {
+        Ok(a) => a,
+        Err(e) => {
+            error!("{}", e);
             return;
         }
     };
 
-    let path = RelativePath::new(s);
+    let path = RelativePath::new(&s);
     let display = path.to_string();
 
-    let file = match File::open(path.to_path(".")) {
+    let file_path = path.to_path(".");
+    let file = match File::open(&file_path) {
         Ok(file) => file,
         Err(why) => {
             error!("couldn't open {}: {}", display, why);
             return;
         }
     };
-    repl::start(file, stdout());
+    if let Some(dir) = file_path.parent() {
+        config.dir = dir.to_path_buf();
+    }
+    repl::start(file, stdout(), config);
+}
+
+// soundbytes [--volume <dB>] [--limit <dB>] [--gain <track>=<dB>]... [--mute <track>]... <file>
+fn parse_args(args: Vec<String>) -> Result<(String, Config), String> {
+    let mut config = Config::default();
+    let mut input = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--volume" => {
+                let db = args.next().ok_or("--volume expects decibels")?;
+                let db = db.parse().map_err(|_| format!("invalid volume `{}`", db))?;
+                config.volume = Some(db);
+            }
+            "--limit" => {
+                let db = args.next().ok_or("--limit expects decibels")?;
+                let db = db
+                    .parse()
+                    .map_err(|_| format!("invalid limiter threshold `{}`", db))?;
+                config.limit = Some(db);
+            }
+            "--gain" => {
+                let gain = args.next().ok_or("--gain expects <track>=<dB>")?;
+                let mut spl = gain.splitn(2, '=');
+                let (name, db) = match (spl.next(), spl.next()) {
+                    (Some(name), Some(db)) => (name, db),
+                    _ => return Err(format!("invalid gain `{}`, expected <track>=<dB>", gain)),
+                };
+                let db = db
+                    .parse()
+                    .map_err(|_| format!("invalid gain `{}`, expected <track>=<dB>", gain))?;
+                config.gains.insert(name.to_string(), db);
+            }
+            "--mute" => {
+                let name = args.next().ok_or("--mute expects a track name")?;
+                config.mutes.insert(name);
+            }
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    match input {
+        Some(i) => Ok((i, config)),
+        None => Err("no input given".to_string()),
+    }
 }
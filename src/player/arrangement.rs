@@ -0,0 +1,393 @@
+use crate::player::effect::ProcessorBox;
+use crate::player::frame::Frame;
+use crate::player::instrument::{Instrument, InstrumentBox, Synth};
+use crate::player::tempo::{calc_duration, Duration, Rates};
+use std::collections::VecDeque;
+
+/// Processors shared by the parts of a sequence or a stack,
+/// they run once on the mix of the parts.
+#[derive(Debug, Clone, Default)]
+struct Bus {
+    chain: Vec<ProcessorBox>,
+    // samples left for the processors to ring out once the parts are over
+    tail: Option<f32>,
+}
+
+impl Bus {
+    fn process(
+        &mut self,
+        frame: Frame,
+        parts_finished: bool,
+        sample_rate: f32,
+        beat_per_min: f32,
+    ) -> Frame {
+        if parts_finished {
+            let tail = match self.tail {
+                Some(t) => t,
+                None => {
+                    self.chain
+                        .iter()
+                        .map(|p| p.tail(sample_rate, beat_per_min))
+                        .fold(0.0, f32::max)
+                        * sample_rate
+                }
+            };
+            self.tail = Some(tail - 1.0);
+        }
+        let mut frame = frame;
+        for processor in self.chain.iter_mut() {
+            frame = processor.process(frame, sample_rate, beat_per_min);
+        }
+        frame
+    }
+
+    fn is_finished(&self, parts_finished: bool) -> bool {
+        parts_finished && (self.chain.is_empty() || self.tail.is_some_and(|t| t <= 0.0))
+    }
+}
+
+/// Plays its parts one after the other.
+/// A part starts as soon as the notes of the previous one are over,
+/// while its effects ring out.
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    parts: VecDeque<InstrumentBox>,
+    ringing: Vec<InstrumentBox>,
+    bus: Bus,
+}
+
+impl Sequence {
+    pub fn new(parts: Vec<InstrumentBox>) -> Self {
+        Self {
+            parts: VecDeque::from(parts),
+            ringing: vec![],
+            bus: Bus::default(),
+        }
+    }
+
+    fn parts_finished(&self) -> bool {
+        self.parts.is_empty() && self.ringing.is_empty()
+    }
+}
+
+impl Instrument for Sequence {
+    fn next_frame(&mut self, sample_rate: f32, beat_per_min: f32) -> Frame {
+        while let Some(part) = self.parts.front() {
+            if !part.notes_finished() {
+                break;
+            }
+            let part = self.parts.pop_front().unwrap();
+            self.ringing.push(part);
+        }
+
+        let mut frame = Frame::default();
+        if let Some(part) = self.parts.front_mut() {
+            frame += part.next_frame(sample_rate, beat_per_min);
+        }
+        for part in self.ringing.iter_mut() {
+            frame += part.next_frame(sample_rate, beat_per_min);
+        }
+        self.ringing.retain(|p| !p.is_finished());
+        let parts_finished = self.parts_finished();
+        self.bus
+            .process(frame, parts_finished, sample_rate, beat_per_min)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.bus.is_finished(self.parts_finished())
+    }
+
+    fn notes_finished(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    fn voices(&self) -> usize {
+        self.parts
+            .iter()
+            .chain(self.ringing.iter())
+            .map(|p| p.voices())
+            .sum()
+    }
+
+    fn synths_mut(&mut self, f: &mut dyn FnMut(&mut Synth)) {
+        for part in self.parts.iter_mut().chain(self.ringing.iter_mut()) {
+            part.synths_mut(f);
+        }
+    }
+
+    fn push_processor(&mut self, processor: ProcessorBox) {
+        self.bus.chain.push(processor);
+    }
+}
+
+/// Plays its parts at the same time.
+#[derive(Debug, Clone)]
+pub struct Stack {
+    parts: Vec<InstrumentBox>,
+    bus: Bus,
+}
+
+impl Stack {
+    pub fn new(parts: Vec<InstrumentBox>) -> Self {
+        Self {
+            parts,
+            bus: Bus::default(),
+        }
+    }
+
+    fn parts_finished(&self) -> bool {
+        self.parts.iter().all(|p| p.is_finished())
+    }
+}
+
+impl Instrument for Stack {
+    fn next_frame(&mut self, sample_rate: f32, beat_per_min: f32) -> Frame {
+        let mut frame = Frame::default();
+        for part in self.parts.iter_mut() {
+            if !part.is_finished() {
+                frame += part.next_frame(sample_rate, beat_per_min);
+            }
+        }
+        let parts_finished = self.parts_finished();
+        self.bus
+            .process(frame, parts_finished, sample_rate, beat_per_min)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.bus.is_finished(self.parts_finished())
+    }
+
+    fn notes_finished(&self) -> bool {
+        self.parts.iter().all(|p| p.notes_finished())
+    }
+
+    fn voices(&self) -> usize {
+        self.parts.iter().map(|p| p.voices()).sum()
+    }
+
+    fn synths_mut(&mut self, f: &mut dyn FnMut(&mut Synth)) {
+        for part in self.parts.iter_mut() {
+            part.synths_mut(f);
+        }
+    }
+
+    fn push_processor(&mut self, processor: ProcessorBox) {
+        self.bus.chain.push(processor);
+    }
+}
+
+/// Starts its part after a rest, the length of the rest follows the tempo.
+#[derive(Debug, Clone)]
+pub struct Offset {
+    part: InstrumentBox,
+    delay: Duration,
+    // samples left before the part starts, known once the sample rate is
+    wait: Option<f32>,
+}
+
+impl Offset {
+    pub fn new(part: InstrumentBox, delay: Duration) -> Self {
+        Self {
+            part,
+            delay,
+            wait: None,
+        }
+    }
+}
+
+impl Instrument for Offset {
+    fn next_frame(&mut self, sample_rate: f32, beat_per_min: f32) -> Frame {
+        let wait = match self.wait {
+            Some(w) => w,
+            None => calc_duration(Rates {
+                sample_rate,
+                beat_per_min,
+                duration: &self.delay,
+            }),
+        };
+        if wait > 0.0 {
+            self.wait = Some(wait - 1.0);
+            return Frame::default();
+        }
+        self.wait = Some(wait);
+        self.part.next_frame(sample_rate, beat_per_min)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.part.is_finished()
+    }
+
+    fn notes_finished(&self) -> bool {
+        self.part.notes_finished()
+    }
+
+    fn voices(&self) -> usize {
+        self.part.voices()
+    }
+
+    fn synths_mut(&mut self, f: &mut dyn FnMut(&mut Synth)) {
+        self.part.synths_mut(f);
+    }
+
+    fn push_processor(&mut self, processor: ProcessorBox) {
+        self.part.push_processor(processor);
+    }
+}
+
+/// A part of a song, playing at its own tempo when it has one.
+#[derive(Debug, Clone)]
+pub struct Section {
+    part: InstrumentBox,
+    beat_per_min: Option<f32>,
+}
+
+impl Section {
+    pub fn new(part: InstrumentBox, beat_per_min: Option<f32>) -> Self {
+        Self { part, beat_per_min }
+    }
+}
+
+impl Instrument for Section {
+    fn next_frame(&mut self, sample_rate: f32, beat_per_min: f32) -> Frame {
+        let beat_per_min = self.beat_per_min.unwrap_or(beat_per_min);
+        self.part.next_frame(sample_rate, beat_per_min)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.part.is_finished()
+    }
+
+    fn notes_finished(&self) -> bool {
+        self.part.notes_finished()
+    }
+
+    fn voices(&self) -> usize {
+        self.part.voices()
+    }
+
+    fn synths_mut(&mut self, f: &mut dyn FnMut(&mut Synth)) {
+        self.part.synths_mut(f);
+    }
+
+    fn push_processor(&mut self, processor: ProcessorBox) {
+        self.part.push_processor(processor);
+    }
+}
+
+#[cfg(test)]
+use crate::player::instrument::new_test_opts;
+#[cfg(test)]
+use crate::player::sound::{Letter, Note, Octave, Sound};
+
+#[cfg(test)]
+fn quarters(count: usize) -> InstrumentBox {
+    let score = (0..count)
+        .map(|_| {
+            vec![Sound::new(
+                Note::new(Letter::A, 0),
+                Octave::Four,
+                Duration::note(4),
+            )]
+        })
+        .collect();
+    Box::new(Synth::new(new_test_opts(), score))
+}
+
+#[cfg(test)]
+fn render(mut ins: InstrumentBox) -> Vec<usize> {
+    let mut voices = vec![];
+    while !ins.is_finished() {
+        ins.next_frame(8000.0, 120.0);
+        voices.push(ins.voices());
+        assert!(voices.len() < 80000, "never finished");
+    }
+    voices
+}
+
+#[test]
+fn test_sequence_and_stack_lengths() {
+    // a quarter note at 120 bpm
+    let slot: usize = 4000;
+
+    // every part may run a few samples over
+    let seq = render(Box::new(Sequence::new(vec![quarters(2), quarters(1)])));
+    assert!((seq.len() as i32 - 3 * slot as i32).abs() <= 8);
+    assert_eq!(1, seq[slot * 5 / 2]);
+
+    let stack = render(Box::new(Stack::new(vec![quarters(2), quarters(1)])));
+    assert!((stack.len() as i32 - 2 * slot as i32).abs() <= 4);
+    assert_eq!(2, stack[slot / 2]);
+    assert_eq!(1, stack[slot * 3 / 2]);
+}
+
+#[test]
+fn test_offset_delays_the_part() {
+    let slot: usize = 4000;
+
+    let late = render(Box::new(Offset::new(quarters(1), Duration::new(3, 8))));
+    assert!((late.len() as i32 - (slot as f32 * 2.5) as i32).abs() <= 4);
+    assert_eq!(0, late[slot]);
+    assert_eq!(1, late[slot * 2]);
+
+    let stack = render(Box::new(Stack::new(vec![
+        quarters(1),
+        Box::new(Offset::new(quarters(1), Duration::note(8))),
+    ])));
+    assert_eq!(1, stack[slot / 4]);
+    assert_eq!(2, stack[slot * 3 / 4]);
+    assert_eq!(1, stack[slot * 5 / 4]);
+}
+
+#[test]
+fn test_sections_keep_their_tempo() {
+    let slot: usize = 4000;
+
+    // a quarter note at 60 bpm then one at the tempo of the song
+    let song = render(Box::new(Sequence::new(vec![
+        Box::new(Section::new(quarters(1), Some(60.0))),
+        Box::new(Section::new(quarters(1), None)),
+    ])));
+    assert!((song.len() as i32 - 3 * slot as i32).abs() <= 8);
+}
+
+#[cfg(test)]
+use crate::player::effect::Processor;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::Arc;
+
+// Counts the frames it processes, and rings for a quarter of a second.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+struct Counter(Arc<AtomicUsize>);
+
+#[cfg(test)]
+impl Processor for Counter {
+    fn process(&mut self, frame: Frame, _sample_rate: f32, _beat_per_min: f32) -> Frame {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        frame
+    }
+
+    fn tail(&self, _sample_rate: f32, _beat_per_min: f32) -> f32 {
+        0.25
+    }
+}
+
+#[test]
+fn test_parts_share_one_bus() {
+    let slot: usize = 4000;
+
+    for mut ins in [
+        Box::new(Stack::new(vec![quarters(2), quarters(1)])) as InstrumentBox,
+        Box::new(Sequence::new(vec![quarters(1), quarters(1)])),
+    ] {
+        let frames = Arc::new(AtomicUsize::new(0));
+        ins.push_processor(Box::new(Counter(frames.clone())));
+        let played = render(ins);
+
+        // every frame goes through the bus once, the tail rings after the parts
+        assert_eq!(played.len(), frames.load(Ordering::Relaxed));
+        assert!((played.len() as i32 - (2 * slot + 2000) as i32).abs() <= 8);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::player::effect::Processor;
+use crate::player::frame::Frame;
+use crate::player::tempo::{calc_duration, Duration, Rates};
+use std::f32::consts::PI;
+
+const MAX_TAIL: f32 = 10.0;
+
+/// Echo with its time given as a note value, so it follows the tempo.
+#[derive(Debug, Clone)]
+pub struct Delay {
+    time: Duration,
+    feedback: f32,
+    mix: f32,
+    cutoff: f32,
+    ping_pong: bool,
+    left: Vec<f32>,
+    right: Vec<f32>,
+    pos: usize,
+    // low-pass state of the repeats
+    lp: Frame,
+}
+
+impl Delay {
+    /// `feedback` and `mix` go from 0.0 to 1.0,
+    /// `cutoff` is the frequency above which the repeats are damped.
+    pub fn new(time: Duration, feedback: f32, mix: f32, cutoff: f32, ping_pong: bool) -> Self {
+        Self {
+            time,
+            feedback,
+            mix,
+            cutoff,
+            ping_pong,
+            left: vec![],
+            right: vec![],
+            pos: 0,
+            lp: Frame::default(),
+        }
+    }
+
+    fn resize(&mut self, len: usize) {
+        self.left = vec![0.0; len];
+        self.right = vec![0.0; len];
+        self.pos = 0;
+    }
+}
+
+impl Processor for Delay {
+    fn process(&mut self, frame: Frame, sample_rate: f32, beat_per_min: f32) -> Frame {
+        let len = calc_duration(Rates {
+            sample_rate,
+            beat_per_min,
+            duration: &self.time,
+        }) as usize;
+        if len == 0 {
+            return frame;
+        }
+        if len != self.left.len() {
+            self.resize(len);
+        }
+
+        let echo = Frame::new(self.left[self.pos], self.right[self.pos]);
+        let a = 1.0 - (-2.0 * PI * self.cutoff / sample_rate).exp();
+        self.lp += (echo - self.lp) * a;
+        let fb = self.lp * self.feedback;
+
+        if self.ping_pong {
+            // the input enters on the left and every repeat swaps sides
+            self.left[self.pos] = frame.mono() + fb.right;
+            self.right[self.pos] = fb.left;
+        } else {
+            self.left[self.pos] = frame.left + fb.left;
+            self.right[self.pos] = frame.right + fb.right;
+        }
+        self.pos = (self.pos + 1) % len;
+
+        frame * (1.0 - self.mix) + echo * self.mix
+    }
+
+    fn tail(&self, sample_rate: f32, beat_per_min: f32) -> f32 {
+        let delay_sec = calc_duration(Rates {
+            sample_rate,
+            beat_per_min,
+            duration: &self.time,
+        }) / sample_rate;
+        if self.feedback <= 0.0 {
+            return delay_sec;
+        }
+        // repeats until the echo has decayed by 60dB
+        let repeats = (0.001_f32).ln() / self.feedback.ln();
+        (delay_sec * (repeats + 1.0)).min(MAX_TAIL)
+    }
+}
+
+#[test]
+fn test_delay_follows_the_tempo() {
+    let sample_rate = 8000.0;
+    // an 8th note at 120 bpm
+    let time = 2000;
+    let mut delay = Delay::new(Duration::note(8), 0.5, 1.0, 20000.0, true);
+
+    let mut out = vec![delay.process(Frame::new(1.0, 1.0), sample_rate, 120.0)];
+    for _ in 0..3 * time {
+        out.push(delay.process(Frame::default(), sample_rate, 120.0));
+    }
+    let first = out.iter().position(|f| f.left.abs() > 0.1).unwrap();
+    assert_eq!(time, first);
+    assert!(out[time].right.abs() < 1e-6);
+    // the repeat bounces to the right, half as loud
+    assert!((out[2 * time].right - 0.5).abs() < 0.01);
+    assert!(out[2 * time].left.abs() < 1e-6);
+    assert!((delay.tail(sample_rate, 120.0) - 0.25 * 10.97).abs() < 0.05);
+}
+
+#[test]
+fn test_delay_tail_before_playing() {
+    // the master bus asks for the tail before any frame went through
+    let delay = Delay::new(Duration::note(8), 0.5, 1.0, 20000.0, false);
+    assert!((delay.tail(8000.0, 120.0) - 0.25 * 10.97).abs() < 0.05);
+    assert!((delay.tail(8000.0, 60.0) - 0.5 * 10.97).abs() < 0.1);
+
+    let single = Delay::new(Duration::note(4), 0.0, 1.0, 20000.0, false);
+    assert!((single.tail(44100.0, 120.0) - 0.5).abs() < 1e-3);
+}
@@ -1,10 +1,36 @@
-use crate::player::tempo::SampleClock;
+use crate::player::frame::Frame;
+use crate::player::pitch::Pitch;
+use crate::player::tempo::{calc_duration, Duration, Rates, SampleClock};
+use std::f32::consts::PI;
 use std::fmt::Debug;
 
 pub type EffectBox = Box<dyn Effect>;
+pub type ProcessorBox = Box<dyn Processor>;
 
-pub trait Effect: Debug + Send + CloneEffect + 'static {
-    fn get_frequency(&self, sample_clock: &SampleClock) -> f32;
+pub trait Effect: Debug + Send + Sync + CloneEffect + 'static {
+    /// The pitch of the sound once the effect has moved it.
+    fn get_pitch(&self, pitch: Pitch, _sample_clock: &SampleClock, _rates: &SoundRates) -> Pitch {
+        pitch
+    }
+    /// Factor the amplitude of the sound is multiplied by.
+    fn get_amplitude(&self, _sample_clock: &SampleClock, _rates: &SoundRates) -> f32 {
+        1.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SoundRates {
+    pub(crate) sample_rate: f32,
+    pub(crate) beat_per_min: f32,
+    /// Samples the track had played when the sound started.
+    pub(crate) start: f32,
+}
+
+impl SoundRates {
+    /// Samples since the start of the track, so LFOs keep running from one note to the next.
+    pub(crate) fn track_clock(&self, sample_clock: &SampleClock) -> f32 {
+        self.start + sample_clock.get_clock()
+    }
 }
 
 pub trait CloneEffect {
@@ -26,23 +52,262 @@ impl Clone for EffectBox {
     }
 }
 
+// Scales of the `vib` arguments, so the scripts keep their numbers:
+// a speed of 10 is about 7 Hz and a depth of 5 swings 12.5 cents each way.
+const HZ_PER_SPEED: f32 = 0.7;
+const CENTS_PER_DEPTH: f32 = 2.5;
+
+/// Pitch LFO, `hz` cycles a second swinging `cents` above and below the note.
 #[derive(Debug, Clone)]
 pub struct Vibrato {
-    depth: f32,
-    speed: f32,
+    cents: f32,
+    hz: f32,
 }
 
 impl Vibrato {
     pub fn new(depth: f32, speed: f32) -> Self {
-        Self { depth, speed }
+        Self {
+            cents: depth * CENTS_PER_DEPTH,
+            hz: speed * HZ_PER_SPEED,
+        }
     }
 }
 
 impl Effect for Vibrato {
-    fn get_frequency(&self, sample_clock: &SampleClock) -> f32 {
-        (sample_clock.get_clock() * (self.speed * 0.0001)).sin()
-            * self.depth
-            * sample_clock.get_dur()
-            * 0.0000001
+    fn get_pitch(&self, pitch: Pitch, sample_clock: &SampleClock, rates: &SoundRates) -> Pitch {
+        let lfo = sine_at(self.hz, rates.track_clock(sample_clock), rates.sample_rate);
+        pitch.bent(lfo * self.cents)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BendCurve {
+    Linear,
+    // slow start, like easing into the bend
+    Exponential,
+    // fast start, settling at the target
+    Logarithmic,
+}
+
+impl BendCurve {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "lin" => Some(Self::Linear),
+            "exp" => Some(Self::Exponential),
+            "log" => Some(Self::Logarithmic),
+            _ => None,
+        }
+    }
+
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::Exponential => t * t,
+            Self::Logarithmic => 1.0 - (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+/// Raises the pitch of a sound by `semitones` during the first half of it,
+/// then holds the bent pitch.
+#[derive(Debug, Clone)]
+pub struct Bend {
+    semitones: f32,
+    curve: BendCurve,
+}
+
+impl Bend {
+    pub fn new(semitones: f32, curve: BendCurve) -> Self {
+        Self { semitones, curve }
     }
 }
+
+impl Effect for Bend {
+    fn get_pitch(&self, pitch: Pitch, sample_clock: &SampleClock, _rates: &SoundRates) -> Pitch {
+        let rise = sample_clock.get_dur() * 0.5;
+        let t = (sample_clock.get_clock() / rise).min(1.0);
+        pitch.bent(self.curve.apply(t) * self.semitones * 100.0)
+    }
+}
+
+fn sine_at(hz: f32, clock: f32, sample_rate: f32) -> f32 {
+    (2.0 * PI * hz * clock / sample_rate).sin()
+}
+
+/// Amplitude LFO, one cycle lasts the given note value.
+#[derive(Debug, Clone)]
+pub struct Tremolo {
+    rate: Duration,
+    depth: f32,
+}
+
+impl Tremolo {
+    pub fn new(rate: Duration, depth: f32) -> Self {
+        Self { rate, depth }
+    }
+}
+
+impl Effect for Tremolo {
+    fn get_amplitude(&self, sample_clock: &SampleClock, rates: &SoundRates) -> f32 {
+        let period = calc_duration(Rates {
+            sample_rate: rates.sample_rate,
+            beat_per_min: rates.beat_per_min,
+            duration: &self.rate,
+        });
+        let phase = rates.track_clock(sample_clock) / period;
+        1.0 - self.depth * (0.5 - 0.5 * (2.0 * PI * phase).cos())
+    }
+}
+
+/// Classic amplitude modulation by a sine, normalized to keep the peak level.
+#[derive(Debug, Clone)]
+pub struct AmplitudeMod {
+    hz: f32,
+    depth: f32,
+}
+
+impl AmplitudeMod {
+    pub fn new(hz: f32, depth: f32) -> Self {
+        Self { hz, depth }
+    }
+}
+
+impl Effect for AmplitudeMod {
+    fn get_amplitude(&self, sample_clock: &SampleClock, rates: &SoundRates) -> f32 {
+        let modulator = sine_at(self.hz, rates.track_clock(sample_clock), rates.sample_rate);
+        (1.0 + self.depth * modulator) / (1.0 + self.depth)
+    }
+}
+
+/// Multiplies the sound by a sine, leaving only the sum and difference frequencies.
+#[derive(Debug, Clone)]
+pub struct RingMod {
+    hz: f32,
+    mix: f32,
+}
+
+impl RingMod {
+    pub fn new(hz: f32, mix: f32) -> Self {
+        Self { hz, mix }
+    }
+}
+
+impl Effect for RingMod {
+    fn get_amplitude(&self, sample_clock: &SampleClock, rates: &SoundRates) -> f32 {
+        let carrier = sine_at(self.hz, rates.track_clock(sample_clock), rates.sample_rate);
+        1.0 - self.mix + self.mix * carrier
+    }
+}
+
+/// Processes the signal of a whole track,
+/// as opposed to an `Effect` which only changes the frequency of a sound.
+pub trait Processor: Debug + Send + CloneProcessor + 'static {
+    fn process(&mut self, frame: Frame, sample_rate: f32, beat_per_min: f32) -> Frame;
+    /// Seconds the processor keeps sounding after the track has no more notes.
+    fn tail(&self, _sample_rate: f32, _beat_per_min: f32) -> f32 {
+        0.0
+    }
+}
+
+pub trait CloneProcessor {
+    fn clone_processor(&self) -> ProcessorBox;
+}
+
+impl<T> CloneProcessor for T
+where
+    T: Processor + Clone + 'static,
+{
+    fn clone_processor(&self) -> ProcessorBox {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for ProcessorBox {
+    fn clone(&self) -> Self {
+        self.clone_processor()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Drive {
+    amount: f32,
+    norm: f32,
+}
+
+impl Drive {
+    /// `amount` goes from 0.0 (clean) to 1.0 (heavily saturated).
+    pub fn new(amount: f32) -> Self {
+        let amount = 1.0 + amount * 10.0;
+        Self {
+            amount,
+            norm: amount.tanh(),
+        }
+    }
+
+    fn shape(&self, sample: f32) -> f32 {
+        (sample * self.amount).tanh() / self.norm
+    }
+}
+
+impl Processor for Drive {
+    fn process(&mut self, frame: Frame, _sample_rate: f32, _beat_per_min: f32) -> Frame {
+        Frame::new(self.shape(frame.left), self.shape(frame.right))
+    }
+}
+
+#[cfg(test)]
+use crate::player::sound::{Letter, Note, Octave};
+
+#[test]
+fn test_lfos_run_on_the_track_clock() {
+    let rates = |start| SoundRates {
+        sample_rate: 8000.0,
+        beat_per_min: 120.0,
+        start,
+    };
+    let mut first = SampleClock::new(2000.0);
+    for _ in 0..1500 {
+        first.update_clock();
+    }
+    let second = SampleClock::new(2000.0);
+
+    // one cycle per quarter note, a note starting half way is at the bottom
+    let tremolo = Tremolo::new(Duration::note(4), 0.5);
+    assert!((tremolo.get_amplitude(&second, &rates(2000.0)) - 0.5).abs() < 1e-4);
+
+    // a note goes on from where the one before it left the LFO
+    let am = AmplitudeMod::new(3.0, 1.0);
+    assert!(
+        (am.get_amplitude(&first, &rates(0.0)) - am.get_amplitude(&second, &rates(1500.0))).abs()
+            < 1e-6
+    );
+}
+
+#[test]
+fn test_vibrato_swings_the_pitch() {
+    let rates = SoundRates {
+        sample_rate: 8000.0,
+        beat_per_min: 120.0,
+        start: 0.0,
+    };
+    // the arguments of vib(10, 5, ...)
+    let vibrato = Vibrato::new(5.0, 10.0);
+    let a = Pitch::new(Note::new(Letter::A, 0), Octave::Four).unwrap();
+    let mut clock = SampleClock::new(8000.0);
+    let mut offsets = vec![];
+    for _ in 0..8000 {
+        offsets.push(vibrato.get_pitch(a, &clock, &rates).cents());
+        clock.update_clock();
+    }
+
+    // 12.5 cents around the a, seven times a second
+    let highest = offsets.iter().cloned().fold(f32::MIN, f32::max);
+    let lowest = offsets.iter().cloned().fold(f32::MAX, f32::min);
+    assert!((highest - 12.5).abs() < 0.01);
+    assert!((lowest + 12.5).abs() < 0.01);
+    let falling = offsets
+        .windows(2)
+        .filter(|w| w[0] > 0.0 && w[1] <= 0.0)
+        .count();
+    assert_eq!(7, falling);
+}
@@ -0,0 +1,162 @@
+use crate::player::tempo::SampleClock;
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy)]
+pub enum FilterKind {
+    Low,
+    High,
+    Band,
+}
+
+impl FilterKind {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "lp" | "lowpass" => Some(Self::Low),
+            "hp" | "highpass" => Some(Self::High),
+            "bp" | "bandpass" => Some(Self::Band),
+            _ => None,
+        }
+    }
+}
+
+/// ADSR moving the cutoff and resonance of a filter over the life of a note.
+/// Times are in seconds, the release happens at the end of the note.
+#[derive(Debug, Clone)]
+pub struct FilterEnvelope {
+    pub(crate) attack: f32,
+    pub(crate) decay: f32,
+    pub(crate) sustain: f32,
+    pub(crate) release: f32,
+    /// Semitones the cutoff moves at the peak of the envelope.
+    pub(crate) amount: f32,
+    /// Resonance added at the peak of the envelope, from -1.0 to 1.0.
+    pub(crate) resonance: f32,
+}
+
+impl FilterEnvelope {
+    pub fn get_level(&self, sample_clock: &SampleClock, sample_rate: f32) -> f32 {
+        let life_time = sample_clock.get_clock() / sample_rate;
+        let end = sample_clock.get_dur() / sample_rate;
+
+        let level = if life_time < self.attack {
+            life_time / self.attack
+        } else if life_time < self.attack + self.decay {
+            1.0 - (life_time - self.attack) / self.decay * (1.0 - self.sustain)
+        } else {
+            self.sustain
+        };
+
+        let release_start = end - self.release;
+        if life_time > release_start && self.release > 0.0 {
+            return level * ((end - life_time) / self.release).max(0.0);
+        }
+        level
+    }
+}
+
+/// Resonant state variable filter, the trapezoidal integrated version by Andrew Simper.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    kind: FilterKind,
+    cutoff: f32,
+    resonance: f32,
+    env: Option<FilterEnvelope>,
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl Filter {
+    /// `cutoff` is in Hz and `resonance` goes from 0.0 to 1.0.
+    pub fn new(kind: FilterKind, cutoff: f32, resonance: f32, env: Option<FilterEnvelope>) -> Self {
+        Self {
+            kind,
+            cutoff,
+            resonance,
+            env,
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32, sample_clock: &SampleClock, sample_rate: f32) -> f32 {
+        let mut cutoff = self.cutoff;
+        let mut resonance = self.resonance;
+        if let Some(env) = &self.env {
+            let level = env.get_level(sample_clock, sample_rate);
+            cutoff *= (level * env.amount / 12.0).exp2();
+            resonance = (resonance + level * env.resonance).clamp(0.0, 1.0);
+        }
+        let cutoff = cutoff.max(10.0).min(sample_rate * 0.49);
+
+        let g = (PI * cutoff / sample_rate).tan();
+        // damping goes from 2.0 (no resonance) towards 0.0 (self oscillation)
+        let k = 2.0 - 1.95 * resonance;
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = sample - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        match self.kind {
+            FilterKind::Low => v2,
+            FilterKind::Band => v1,
+            FilterKind::High => sample - k * v1 - v2,
+        }
+    }
+}
+
+#[cfg(test)]
+fn response(kind: FilterKind, resonance: f32, freq: f32) -> f32 {
+    response_with(Filter::new(kind, 1000.0, resonance, None), freq)
+}
+
+#[cfg(test)]
+fn response_with(mut filter: Filter, freq: f32) -> f32 {
+    let sample_rate = 44100.0;
+    let clock = SampleClock::new(sample_rate);
+    let mut peak = 0.0_f32;
+    for i in 0..44100 {
+        let input = (2.0 * PI * freq * i as f32 / sample_rate).sin();
+        let out = filter.process(input, &clock, sample_rate);
+        // skip the transient
+        if i > 22050 {
+            peak = peak.max(out.abs());
+        }
+    }
+    peak
+}
+
+#[test]
+fn test_filter_response() {
+    assert!((response(FilterKind::Low, 0.0, 100.0) - 1.0).abs() < 0.05);
+    assert!(response(FilterKind::Low, 0.0, 8000.0) < 0.03);
+    assert!(response(FilterKind::High, 0.0, 100.0) < 0.03);
+    assert!((response(FilterKind::High, 0.0, 8000.0) - 1.0).abs() < 0.05);
+    assert!(response(FilterKind::Band, 0.0, 100.0) < 0.15);
+    assert!(response(FilterKind::Band, 0.0, 1000.0) > 0.45);
+    // the resonance peaks at the cutoff
+    assert!(response(FilterKind::Low, 0.9, 1000.0) > 3.0);
+}
+
+#[test]
+fn test_resonance_follows_the_envelope() {
+    // the clock of the test never moves, so the envelope stays at its sustain
+    let env = |sustain: f32| {
+        Some(FilterEnvelope {
+            attack: 0.0,
+            decay: 0.0,
+            sustain,
+            release: 0.0,
+            amount: 0.0,
+            resonance: 0.9,
+        })
+    };
+    let open = response_with(Filter::new(FilterKind::Low, 1000.0, 0.0, env(1.0)), 1000.0);
+    let closed = response_with(Filter::new(FilterKind::Low, 1000.0, 0.0, env(0.0)), 1000.0);
+    assert!(open > 3.0);
+    assert!((closed - response(FilterKind::Low, 0.0, 1000.0)).abs() < 1e-3);
+}
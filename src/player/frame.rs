@@ -0,0 +1,61 @@
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+/// A single stereo sample.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Frame {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Frame {
+    pub fn new(left: f32, right: f32) -> Self {
+        Self { left, right }
+    }
+
+    /// Places a mono sample in the stereo field.
+    /// `pan` goes from -1.0 (hard left) to 1.0 (hard right).
+    /// Constant power law, normalized so a centered sample keeps its level.
+    pub fn panned(sample: f32, pan: f32) -> Self {
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        Self {
+            left: sample * angle.cos() * SQRT_2,
+            right: sample * angle.sin() * SQRT_2,
+        }
+    }
+
+    pub fn mono(&self) -> f32 {
+        (self.left + self.right) * 0.5
+    }
+}
+
+impl Add for Frame {
+    type Output = Frame;
+
+    fn add(self, rhs: Frame) -> Frame {
+        Frame::new(self.left + rhs.left, self.right + rhs.right)
+    }
+}
+
+impl Sub for Frame {
+    type Output = Frame;
+
+    fn sub(self, rhs: Frame) -> Frame {
+        Frame::new(self.left - rhs.left, self.right - rhs.right)
+    }
+}
+
+impl AddAssign for Frame {
+    fn add_assign(&mut self, rhs: Frame) {
+        self.left += rhs.left;
+        self.right += rhs.right;
+    }
+}
+
+impl Mul<f32> for Frame {
+    type Output = Frame;
+
+    fn mul(self, rhs: f32) -> Frame {
+        Frame::new(self.left * rhs, self.right * rhs)
+    }
+}
@@ -1,14 +1,70 @@
 use super::sound::Sound;
-use crate::player::effect::EffectBox;
+use crate::player::clamp::Clamp;
+use crate::player::effect::{EffectBox, ProcessorBox, SoundRates};
+use crate::player::filter::Filter;
+use crate::player::frame::Frame;
+use crate::player::mixer::db_to_gain;
 use crate::player::oscillator::OscillatorBox;
+use crate::player::pitch::Pitch;
 use crate::player::sound::Envelope;
 use crate::player::tempo::{calc_duration, Rates, SampleClock};
+use crate::player::tuning::Tuning;
 use std::collections::VecDeque;
+use std::f32::consts::PI;
 use std::fmt::Debug;
+use std::sync::Arc;
+
+// Glide time in seconds of legato notes on a track without a glide time.
+const LEGATO_GLIDE: f32 = 0.06;
+// Seconds a note played without an attack fades out over when no tied note follows it.
+const TIED_RELEASE: f32 = 0.02;
 
 pub trait Instrument: Debug + Send + CloneIns {
-    fn next_freq(&mut self, sample_rate: f32, beat_per_min: f32) -> f32;
+    fn next_frame(&mut self, sample_rate: f32, beat_per_min: f32) -> Frame;
     fn is_finished(&self) -> bool;
+    /// The notes are over, the effects may still be ringing out.
+    fn notes_finished(&self) -> bool;
+    /// Number of sounds currently playing.
+    fn voices(&self) -> usize;
+    fn synths_mut(&mut self, f: &mut dyn FnMut(&mut Synth));
+    /// Runs the whole output of the instrument through the processor.
+    fn push_processor(&mut self, processor: ProcessorBox);
+
+    /// Changes every sound of the instrument, returns false as soon as `f` does.
+    /// Synths sharing a score, like the repeats of a track, have it changed
+    /// once and keep sharing it.
+    fn sounds_mut(&mut self, f: &mut dyn FnMut(&mut Sound) -> bool) -> bool {
+        // (score before the change, score after it)
+        let mut changed: Vec<(Score, Score)> = vec![];
+        let mut ok = true;
+        self.synths_mut(&mut |synth| {
+            if !ok {
+                return;
+            }
+            if let Some((_, new)) = changed
+                .iter()
+                .find(|(old, _)| Arc::ptr_eq(old, &synth.score))
+            {
+                synth.score = new.clone();
+                return;
+            }
+            // a score no other synth holds is changed in place
+            let old = match Arc::strong_count(&synth.score) {
+                1 => None,
+                _ => Some(synth.score.clone()),
+            };
+            for sound in Arc::make_mut(&mut synth.score).iter_mut().flatten() {
+                if !f(sound) {
+                    ok = false;
+                    return;
+                }
+            }
+            if let Some(old) = old {
+                changed.push((old, synth.score.clone()));
+            }
+        });
+        ok
+    }
 }
 
 pub trait CloneIns {
@@ -32,49 +88,173 @@ impl Clone for InstrumentBox {
 
 pub type InstrumentBox = Box<dyn Instrument>;
 pub type Instruments = Vec<InstrumentBox>;
+type Score = Arc<Vec<Vec<Sound>>>;
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub(crate) osc: OscillatorBox,
     pub(crate) env: Envelope,
+    pub(crate) pan: f32,
+    pub(crate) gain: f32,
+    pub(crate) muted: bool,
+    pub(crate) name: Option<String>,
+    pub(crate) chain: Vec<ProcessorBox>,
+    pub(crate) filter: Option<Filter>,
+    /// Seconds every note takes to slide from the pitch of the previous one.
+    pub(crate) glide: f32,
+    pub(crate) tuning: Tuning,
+}
+
+#[derive(Debug, Clone)]
+struct Glide {
+    from: f32,
+    samples: f32,
+}
+
+// What a following note needs to continue from a sound of the last chord.
+#[derive(Debug, Clone)]
+struct Voice {
+    freq: f32,
+    phase: f32,
 }
 
 #[derive(Debug, Clone)]
 struct InnerSound {
     sample_clock: SampleClock,
+    pitch: Option<Pitch>,
+    // of the pitch before any effect, 0 for a rest
     freq: f32,
+    // radians, accumulated so pitch changes stay continuous
+    phase: f32,
+    glide: Option<Glide>,
+    legato: bool,
+    attack: bool,
+    // samples the note fades out over at its end, 0 when the next note continues it
+    release: f32,
+    // position in the last chord started, if it belongs to it
+    voice: Option<usize>,
+    gain: f32,
     effects: Option<Vec<EffectBox>>,
-    sample_rate: f32,
+    filter: Option<Filter>,
+    rates: SoundRates,
 }
 
 impl InnerSound {
-    fn new(sound: Sound, sample_rate: f32, beat_per_min: f32) -> Self {
+    fn new(sound: &Sound, opts: &Options, rates: SoundRates) -> Self {
+        let SoundRates {
+            sample_rate,
+            beat_per_min,
+            ..
+        } = rates;
         let beat_frame_dur = calc_duration(Rates {
             sample_rate,
             beat_per_min,
             duration: &sound.duration,
-        });
+        }) * sound.hold
+            * sound.sounding;
 
-        let freq = sound.note.frequency(sound.octave);
+        let freq = match sound.pitch {
+            Some(p) => p.frequency(&opts.tuning),
+            None => 0.0,
+        };
         let sample_clock = SampleClock::new(beat_frame_dur);
-        let effects = sound.effects;
+        let gain = sound.gain();
+        let effects = sound.effects.clone();
 
         Self {
             sample_clock,
+            pitch: sound.pitch,
             freq,
+            phase: 0.0,
+            glide: None,
+            legato: sound.legato,
+            attack: sound.attack,
+            release: 0.0,
+            voice: None,
+            gain,
             effects,
-            sample_rate,
+            filter: opts.filter.clone(),
+            rates,
+        }
+    }
+
+    fn filter(&mut self, sample: f32) -> f32 {
+        match self.filter.as_mut() {
+            Some(f) => f.process(sample, &self.sample_clock, self.rates.sample_rate),
+            None => sample,
+        }
+    }
+
+    fn amplitude(&self) -> f32 {
+        let effects = match &self.effects {
+            Some(e) => e,
+            None => return self.gain,
+        };
+        let amplitude: f32 = effects
+            .iter()
+            .map(|e| e.get_amplitude(&self.sample_clock, &self.rates))
+            .product();
+        amplitude * self.gain
+    }
+    /// Continues from `prev`, a sound of the previous chord.
+    fn follow(&mut self, prev: &Voice, glide: f32) {
+        // notes played without picking keep the string vibrating
+        if self.legato || !self.attack {
+            self.phase = prev.phase;
+        }
+        // rests neither start nor end a glide
+        if prev.freq <= 0.0 || self.freq <= 0.0 || glide <= 0.0 {
+            return;
         }
+        self.glide = Some(Glide {
+            from: prev.freq,
+            samples: glide * self.rates.sample_rate,
+        });
+    }
+
+    fn base_freq(&self) -> f32 {
+        let glide = match &self.glide {
+            Some(g) => g,
+            None => return self.freq,
+        };
+        let t = self.sample_clock.get_clock() / glide.samples;
+        if t >= 1.0 {
+            return self.freq;
+        }
+        // slide evenly in pitch rather than in Hz
+        glide.from * (self.freq / glide.from).powf(t)
+    }
+
+    fn frequency(&self, tuning: &Tuning) -> f32 {
+        let pitch = match self.pitch {
+            Some(p) => p,
+            None => return 0.0,
+        };
+        let bent = apply_effects(pitch, &self.effects, &self.sample_clock, &self.rates);
+        // a glide scales the pitch from the note it comes from
+        bent.frequency(tuning) * self.base_freq() / self.freq
+    }
+
+    fn envelope(&self, env: &Envelope) -> f32 {
+        if self.attack {
+            return env.get_amplitude(&self.sample_clock);
+        }
+        env.get_tied_amplitude(&self.sample_clock, self.release)
     }
-    fn next_freq(&mut self) -> f32 {
+
+    fn next_sample(&mut self, opts: &mut Options) -> f32 {
         if self.has_ended() {
             return 0.0;
         }
 
-        let sc = &mut self.sample_clock;
-        let freq = apply_effects(self.freq, &self.effects, sc);
-        sc.update_clock();
-        freq
+        let freq = self.frequency(&opts.tuning);
+        let env_f = self.envelope(&opts.env);
+        let osc_f = opts.osc.oscillator(self.phase);
+        let sample = osc_f * env_f * self.amplitude();
+
+        self.phase = (self.phase + 2.0 * PI * freq / self.rates.sample_rate) % (2.0 * PI);
+        self.sample_clock.update_clock();
+        self.filter(sample)
     }
 
     fn has_ended(&self) -> bool {
@@ -84,10 +264,20 @@ impl InnerSound {
 
 #[derive(Debug, Clone)]
 pub struct Synth {
-    score: VecDeque<Vec<Sound>>,
+    // shared by the copies of the track until one of them changes it
+    score: Score,
+    // index of the next chord of the score
+    next: usize,
     cur: Vec<InnerSound>,
-    first_finished: bool,
+    // sounds of the last chord, where the next one continues from
+    last: Vec<Voice>,
+    // samples until the next chord starts
+    slot: f32,
+    // samples played since the track started
+    clock: f32,
     finished: bool,
+    // samples left for the processors to ring out once the score is over
+    tail: f32,
     opts: Options,
 }
 
@@ -95,80 +285,358 @@ pub struct Synth {
 impl Synth {
     pub fn new(opts: Options, score: VecDeque<Vec<Sound>>) -> Self {
         Self {
-            score,
+            score: Arc::new(Vec::from(score)),
+            next: 0,
             cur: vec![],
-            first_finished: true,
+            last: vec![],
+            slot: 0.0,
+            clock: 0.0,
             finished: false,
+            tail: 0.0,
             opts,
         }
     }
+
+    pub fn set_pan(&mut self, pan: f32) {
+        self.opts.pan = pan.my_clamp(-1.0, 1.0);
+    }
+
+    pub fn set_gain_db(&mut self, db: f32) {
+        self.opts.gain = db_to_gain(db);
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.opts.muted = muted;
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.opts.name.as_deref()
+    }
+
+    pub fn sounds(&self) -> impl Iterator<Item = &Sound> {
+        self.score.iter().flatten()
+    }
+
+    pub fn set_glide(&mut self, seconds: f32) {
+        self.opts.glide = seconds.max(0.0);
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.opts.filter = Some(filter);
+    }
+
+    fn process(&mut self, frame: Frame, sample_rate: f32, beat_per_min: f32) -> Frame {
+        let mut frame = frame;
+        for processor in self.opts.chain.iter_mut() {
+            frame = processor.process(frame, sample_rate, beat_per_min);
+        }
+
+        if self.opts.muted {
+            return Frame::default();
+        }
+        frame * self.opts.gain
+    }
+
+    /// Names the track unless it already has a name,
+    /// so tracks derived from a named track keep the original name.
+    pub fn name_once(&mut self, name: &str) {
+        if self.opts.name.is_none() {
+            self.opts.name = Some(name.to_string());
+        }
+    }
 }
 
 impl Instrument for Synth {
-    fn next_freq(&mut self, sample_rate: f32, beat_per_min: f32) -> f32 {
-        let mut first_index: usize = 0;
-        if self.first_finished {
-            if let Some(sounds) = self.score.pop_front() {
-                first_index = self.cur.len();
-
-                for sound in sounds {
-                    self.cur
-                        .push(InnerSound::new(sound, sample_rate, beat_per_min));
+    fn next_frame(&mut self, sample_rate: f32, beat_per_min: f32) -> Frame {
+        if self.slot <= 0.0 {
+            // a handle on the score, so the chord is borrowed rather than copied
+            let score = self.score.clone();
+            if let Some(sounds) = score.get(self.next) {
+                self.next += 1;
+                // the chord lasts as long as its first note is written
+                if let Some(first) = sounds.first() {
+                    self.slot += calc_duration(Rates {
+                        sample_rate,
+                        beat_per_min,
+                        duration: &first.duration,
+                    }) * first.hold;
+                }
+
+                for cur in self.cur.iter_mut() {
+                    cur.voice = None;
                 }
-                self.first_finished = false;
-            } else {
+                let mut last = Vec::with_capacity(sounds.len());
+                for (i, sound) in sounds.iter().enumerate() {
+                    let rates = SoundRates {
+                        sample_rate,
+                        beat_per_min,
+                        start: self.clock,
+                    };
+                    let mut inner = InnerSound::new(sound, &self.opts, rates);
+                    let glide = match (self.opts.glide > 0.0, inner.legato) {
+                        (true, _) => self.opts.glide,
+                        (false, true) => LEGATO_GLIDE,
+                        (false, false) => 0.0,
+                    };
+                    if let Some(prev) = self.last.get(i).or_else(|| self.last.last()) {
+                        inner.follow(prev, glide);
+                    }
+                    let continued = score
+                        .get(self.next)
+                        .and_then(|next| next.get(i).or_else(|| next.last()))
+                        .is_some_and(|next| !next.attack);
+                    if !inner.attack && !continued {
+                        inner.release = TIED_RELEASE * sample_rate;
+                    }
+                    inner.voice = Some(i);
+                    last.push(Voice {
+                        freq: inner.freq,
+                        phase: inner.phase,
+                    });
+                    self.cur.push(inner);
+                }
+                self.last = last;
+            } else if !self.finished && self.cur.is_empty() {
                 self.finished = true;
+                let tail = self
+                    .opts
+                    .chain
+                    .iter()
+                    .map(|p| p.tail(sample_rate, beat_per_min))
+                    .fold(0.0, f32::max);
+                self.tail = tail * sample_rate;
             }
         }
 
         if self.finished {
-            return 0.0;
-        }
-
-        if let Some(cur) = self.cur.get(first_index) {
-            self.first_finished = cur.has_ended();
+            if self.tail <= 0.0 {
+                return Frame::default();
+            }
+            self.tail -= 1.0;
+            return self.process(Frame::default(), sample_rate, beat_per_min);
         }
 
+        self.slot -= 1.0;
+        self.clock += 1.0;
         self.cur.retain(|s| !s.has_ended());
 
         let mut freq = 0.0_f32;
         for cur_sound in self.cur.iter_mut() {
-            freq += apply_options(
-                cur_sound.next_freq(),
-                &mut self.opts,
-                sample_rate,
-                &cur_sound.sample_clock,
-            );
+            freq += cur_sound.next_sample(&mut self.opts);
+            if let Some(v) = cur_sound.voice {
+                self.last[v].phase = cur_sound.phase;
+            }
         }
 
-        freq
+        self.process(
+            Frame::panned(freq, self.opts.pan),
+            sample_rate,
+            beat_per_min,
+        )
     }
 
     fn is_finished(&self) -> bool {
+        self.finished && self.tail <= 0.0
+    }
+
+    fn notes_finished(&self) -> bool {
         self.finished
     }
+
+    fn voices(&self) -> usize {
+        if self.opts.muted || self.finished {
+            return 0;
+        }
+        self.cur.len()
+    }
+
+    fn synths_mut(&mut self, f: &mut dyn FnMut(&mut Synth)) {
+        f(self)
+    }
+
+    fn push_processor(&mut self, processor: ProcessorBox) {
+        self.opts.chain.push(processor);
+    }
 }
 
-fn apply_effects(freq: f32, effects: &Option<Vec<EffectBox>>, sample_clock: &SampleClock) -> f32 {
+fn apply_effects(
+    pitch: Pitch,
+    effects: &Option<Vec<EffectBox>>,
+    sample_clock: &SampleClock,
+    rates: &SoundRates,
+) -> Pitch {
     let effects = match effects {
         Some(e) => e,
-        None => return freq,
+        None => return pitch,
     };
-    let mut eff_f = 0.0;
-    for effect in effects.iter() {
-        eff_f += effect.get_frequency(&sample_clock)
+    effects
+        .iter()
+        .fold(pitch, |p, effect| effect.get_pitch(p, sample_clock, rates))
+}
+
+#[cfg(test)]
+use crate::player::arrangement::Sequence;
+#[cfg(test)]
+use crate::player::effect::{Bend, BendCurve};
+#[cfg(test)]
+use crate::player::oscillator::AnalogSaw;
+#[cfg(test)]
+use crate::player::sound::{Letter, Note, Octave};
+#[cfg(test)]
+use crate::player::tempo::Duration;
+
+#[cfg(test)]
+pub(crate) fn new_test_opts() -> Options {
+    Options {
+        osc: Box::new(AnalogSaw::new()),
+        env: Envelope::new(),
+        pan: 0.0,
+        gain: 1.0,
+        muted: false,
+        name: None,
+        chain: vec![],
+        filter: None,
+        glide: 0.0,
+        tuning: Tuning::equal(),
     }
-    freq + eff_f
 }
 
-fn apply_options(
-    freq: f32,
-    opts: &mut Options,
-    sample_rate: f32,
-    sample_clock: &SampleClock,
-) -> f32 {
-    let env_f = opts.env.get_amplitude(&sample_clock);
-    let osc_f = opts.osc.oscillator(freq, sample_rate, sample_clock);
+#[test]
+fn test_synth_plays_articulations() {
+    let mut bent = Sound::new(Note::new(Letter::A, 0), Octave::Four, Duration::note(16));
+    bent.push_effect(Box::new(Bend::new(2.0, BendCurve::Logarithmic)));
+    let mut slid = Sound::new(Note::new(Letter::C, 0), Octave::Four, Duration::note(16));
+    slid.legato = true;
+    slid.attack = false;
+    let mut hammered = Sound::new(Note::new(Letter::D, 0), Octave::Four, Duration::note(16));
+    hammered.attack = false;
+
+    let score = VecDeque::from(vec![vec![bent], vec![slid], vec![hammered]]);
+    let mut synth = Synth::new(new_test_opts(), score);
+    synth.set_glide(0.01);
+
+    let sample_rate = 8000.0;
+    // a 16th note at 120 bpm
+    let note = 1000;
+    let env = new_test_opts().env;
+    let sustain = env.get_tied_amplitude(&SampleClock::new(1.0), 0.0);
+    // (frequency, envelope) of the newest sound after every frame
+    let mut played = vec![];
+    while !synth.is_finished() {
+        let frame = synth.next_frame(sample_rate, 120.0);
+        assert!(frame.left.is_finite() && frame.right.is_finite());
+        if let Some(cur) = synth.cur.last() {
+            played.push((cur.frequency(&synth.opts.tuning), cur.envelope(&env)));
+        }
+        assert!(played.len() < 10 * note, "synth never finished");
+    }
+    assert!((played.len() as i32 - 3 * note as i32).abs() < 10);
+
+    // the bend reaches two semitones up half way through the note
+    assert!((played[1].0 - 440.0).abs() < 5.0);
+    assert!((played[note * 3 / 4].0 - 493.88).abs() < 1.0);
+
+    // the slide starts from the a and lands on the c
+    assert!((played[note + 1].0 - 440.0).abs() < 10.0);
+    assert!((played[note + note / 2].0 - 261.63).abs() < 1.0);
+    // without a new attack, held until the hammered note continues it
+    assert!((played[note + 1].1 - sustain).abs() < 1e-6);
+    assert!((played[2 * note - 2].1 - sustain).abs() < 1e-6);
+
+    // the hammered note is not attacked again and fades out at the end
+    assert!((played[2 * note + 1].1 - sustain).abs() < 1e-6);
+    assert!((played[2 * note + note / 2].0 - 293.66).abs() < 1.0);
+    assert!(played[played.len() - 2].1 < sustain * 0.1);
+}
+
+#[cfg(test)]
+fn render(score: Vec<Sound>, sample_rate: f32) -> Vec<usize> {
+    let score = score.into_iter().map(|s| vec![s]).collect();
+    let mut synth = Synth::new(new_test_opts(), score);
+    let mut voices = vec![];
+    while !synth.is_finished() {
+        synth.next_frame(sample_rate, 120.0);
+        voices.push(synth.voices());
+    }
+    voices
+}
+
+#[test]
+fn test_articulations_keep_the_timing() {
+    let sample_rate = 8000.0;
+    let note = || Sound::new(Note::new(Letter::A, 0), Octave::Four, Duration::note(4));
+    // a quarter note at 120 bpm
+    let slot = 4000;
+
+    let plain = render(vec![note(), note()], sample_rate);
+
+    let mut short = note();
+    short.sounding = 0.5;
+    let staccato = render(vec![short, note()], sample_rate);
+    assert!((plain.len() as i32 - staccato.len() as i32).abs() <= 2);
+    // silent for the second half of the first note
+    assert_eq!(0, staccato[slot * 3 / 4]);
+    assert_eq!(1, staccato[slot + slot / 2]);
+
+    let mut held = note();
+    held.hold = 2.0;
+    let fermata = render(vec![held, note()], sample_rate);
+    assert!((fermata.len() as i32 - 3 * slot as i32).abs() <= 4);
+}
+
+#[test]
+fn test_velocity_scales_the_output() {
+    let peak = |velocity: Option<u8>| {
+        let mut sound = Sound::new(Note::new(Letter::A, 0), Octave::Four, Duration::note(4));
+        sound.velocity = velocity;
+        let mut synth = Synth::new(new_test_opts(), VecDeque::from(vec![vec![sound]]));
+        let mut peak = 0.0_f32;
+        while !synth.is_finished() {
+            peak = peak.max(synth.next_frame(8000.0, 120.0).left.abs());
+        }
+        peak
+    };
+
+    let full = peak(None);
+    assert!(full > 0.0);
+    assert!((peak(Some(64)) / full - (64.0_f32 / 127.0).powi(2)).abs() < 1e-3);
+}
+
+#[test]
+fn test_copies_share_the_score() {
+    let note = || {
+        vec![Sound::new(
+            Note::new(Letter::A, 0),
+            Octave::Four,
+            Duration::note(4),
+        )]
+    };
+    let synth = Synth::new(new_test_opts(), VecDeque::from(vec![note(), note()]));
+    let mut copy = synth.clone();
+    assert!(Arc::ptr_eq(&synth.score, &copy.score));
+
+    // playing a copy leaves the others at the start
+    copy.next_frame(8000.0, 120.0);
+    assert_eq!(1, copy.next);
+    assert_eq!(0, synth.next);
+
+    // changing a copy gives it its own score
+    assert!(copy.sounds_mut(&mut |sound| sound.transposed(2).map(|s| *sound = s).is_some()));
+    assert!(!Arc::ptr_eq(&synth.score, &copy.score));
+    assert_eq!(Some(69), synth.score[0][0].pitch.as_ref().map(|p| p.key()));
 
-    osc_f * env_f
+    // copies changed together keep sharing a single score
+    let mut repeats: InstrumentBox = Box::new(Sequence::new(vec![
+        Box::new(synth.clone()),
+        Box::new(synth.clone()),
+    ]));
+    repeats.sounds_mut(&mut |sound| {
+        sound.accent = true;
+        true
+    });
+    let mut scores = vec![];
+    repeats.synths_mut(&mut |s| scores.push(s.score.clone()));
+    assert!(Arc::ptr_eq(&scores[0], &scores[1]));
+    assert!(!Arc::ptr_eq(&synth.score, &scores[0]));
+    assert!(scores[0][1][0].accent);
 }
@@ -0,0 +1,129 @@
+use crate::player::frame::Frame;
+use crate::player::mixer::db_to_gain;
+use std::collections::VecDeque;
+
+const LOOKAHEAD_SEC: f32 = 0.005;
+const RELEASE_SEC: f32 = 0.15;
+
+/// Look-ahead peak limiter for the master bus.
+/// The signal is delayed by the look-ahead window so the gain
+/// is already reduced by the time a peak reaches the output.
+#[derive(Debug)]
+pub struct Limiter {
+    threshold: f32,
+    lookahead: usize,
+    attack: f32,
+    release: f32,
+    gain: f32,
+    pos: usize,
+    delay: VecDeque<Frame>,
+    // (position, required gain) with increasing gains, the front is the window minimum
+    window: VecDeque<(usize, f32)>,
+}
+
+impl Limiter {
+    pub fn new(threshold_db: f32, sample_rate: f32) -> Self {
+        let lookahead = ((LOOKAHEAD_SEC * sample_rate) as usize).max(1);
+
+        Self {
+            threshold: db_to_gain(threshold_db),
+            lookahead,
+            attack: 1.0 - (-4.0 / lookahead as f32).exp(),
+            release: 1.0 - (-1.0 / (RELEASE_SEC * sample_rate)).exp(),
+            gain: 1.0,
+            pos: 0,
+            delay: VecDeque::with_capacity(lookahead + 1),
+            window: VecDeque::with_capacity(lookahead + 1),
+        }
+    }
+
+    pub fn process(&mut self, frame: Frame) -> Frame {
+        let required = self.required_gain(frame);
+        while let Some(&(_, g)) = self.window.back() {
+            if g < required {
+                break;
+            }
+            self.window.pop_back();
+        }
+        self.window.push_back((self.pos, required));
+        while let Some(&(p, _)) = self.window.front() {
+            if p + self.lookahead >= self.pos {
+                break;
+            }
+            self.window.pop_front();
+        }
+        self.pos += 1;
+
+        let target = self.window.front().map_or(1.0, |&(_, g)| g);
+        if target < self.gain {
+            self.gain += (target - self.gain) * self.attack;
+        } else {
+            self.gain += (target - self.gain) * self.release;
+        }
+
+        self.delay.push_back(frame);
+        if self.delay.len() <= self.lookahead {
+            return Frame::default();
+        }
+        let out = self.delay.pop_front().unwrap_or_default();
+        // the smoothed gain may still lag behind a steep peak
+        out * self.gain.min(self.required_gain(out))
+    }
+
+    /// Seconds the signal is held back, to flush once the input is over.
+    pub fn tail(&self, sample_rate: f32) -> f32 {
+        self.lookahead as f32 / sample_rate
+    }
+
+    fn required_gain(&self, frame: Frame) -> f32 {
+        let peak = frame.left.abs().max(frame.right.abs());
+        if peak <= self.threshold {
+            return 1.0;
+        }
+        self.threshold / peak
+    }
+}
+
+#[test]
+fn test_limiter_stays_below_threshold() {
+    let mut limiter = Limiter::new(-6.0, 44100.0);
+    let threshold = db_to_gain(-6.0);
+
+    for i in 0..44100 {
+        let sample = (i as f32 * 0.05).sin() * 3.0;
+        let out = limiter.process(Frame::new(sample, -sample));
+        assert!(out.left.abs() <= threshold + 1e-6);
+        assert!(out.right.abs() <= threshold + 1e-6);
+    }
+}
+
+#[test]
+fn test_limiter_passes_quiet_signal() {
+    let mut limiter = Limiter::new(-1.0, 44100.0);
+    let lookahead = (LOOKAHEAD_SEC * 44100.0) as usize;
+    let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+
+    for (i, sample) in input.iter().enumerate() {
+        let out = limiter.process(Frame::new(*sample, *sample));
+        if i >= lookahead {
+            assert!((out.left - input[i - lookahead]).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_limiter_flushes_its_tail() {
+    let sample_rate = 44100.0;
+    let mut limiter = Limiter::new(-1.0, sample_rate);
+    let tail = (limiter.tail(sample_rate) * sample_rate).round() as usize;
+
+    let mut out = vec![];
+    for _ in 0..100 {
+        out.push(limiter.process(Frame::new(0.5, 0.5)));
+    }
+    for _ in 0..tail {
+        out.push(limiter.process(Frame::default()));
+    }
+    let played = out.iter().filter(|f| (f.left - 0.5).abs() < 1e-6).count();
+    assert_eq!(100, played);
+}
@@ -0,0 +1,37 @@
+use crate::player::effect::ProcessorBox;
+
+/// Converts decibels to a linear amplitude factor.
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// Settings of the master bus every instrument is summed into.
+#[derive(Debug, Clone)]
+pub struct Master {
+    pub(crate) volume: f32,
+    pub(crate) threshold_db: f32,
+    pub(crate) chain: Vec<ProcessorBox>,
+}
+
+#[allow(dead_code)]
+impl Master {
+    pub fn new() -> Self {
+        Self {
+            volume: 1.0,
+            threshold_db: -1.0,
+            chain: vec![],
+        }
+    }
+
+    pub fn set_volume_db(&mut self, db: f32) {
+        self.volume = db_to_gain(db);
+    }
+
+    pub fn set_threshold_db(&mut self, db: f32) {
+        self.threshold_db = db.min(0.0);
+    }
+
+    pub fn push_processor(&mut self, processor: ProcessorBox) {
+        self.chain.push(processor);
+    }
+}
@@ -1,8 +1,18 @@
+pub(crate) mod arrangement;
 mod clamp;
+pub(crate) mod delay;
 pub mod effect;
+pub(crate) mod filter;
+pub(crate) mod frame;
 pub mod instrument;
+pub(crate) mod limiter;
+pub(crate) mod mixer;
 pub(crate) mod oscillator;
+pub(crate) mod pitch;
 pub mod play;
+pub(crate) mod reverb;
+pub(crate) mod scale;
 pub(crate) mod song;
 pub(crate) mod sound;
 pub(crate) mod tempo;
+pub(crate) mod tuning;
@@ -1,12 +1,12 @@
 use crate::player::clamp::Clamp;
-use crate::player::tempo::SampleClock;
 use std::f32::consts::PI;
 use std::fmt::Debug;
 
 pub type OscillatorBox = Box<dyn Oscillator>;
 
 pub trait Oscillator: Debug + Send + CloneOsc {
-    fn oscillator(&mut self, hz: f32, sample_rate: f32, sample_clock: &SampleClock) -> f32;
+    /// Value of the wave at `phase`, in radians.
+    fn oscillator(&mut self, phase: f32) -> f32;
 }
 
 pub trait CloneOsc {
@@ -39,12 +39,12 @@ impl AnalogSaw {
 }
 
 impl Oscillator for AnalogSaw {
-    fn oscillator(&mut self, hz: f32, sample_rate: f32, sample_clock: &SampleClock) -> f32 {
+    fn oscillator(&mut self, phase: f32) -> f32 {
         let mut output = 0.0_f32;
 
         for i in 1..40 {
             let n = i as f32;
-            output += (n * to_angular_vel_rate(hz, sample_rate, sample_clock)).sin() / n
+            output += (n * phase).sin() / n
         }
 
         (output * (2.0 / PI)).my_clamp(-1.0, 1.0)
@@ -61,16 +61,9 @@ impl TriangleWave {
     }
 }
 
-fn to_angular_vel_rate(hz: f32, sample_rate: f32, sample_clock: &SampleClock) -> f32 {
-    (2.0 * PI * hz) * sample_clock.get_clock() / sample_rate
-}
-
 impl Oscillator for TriangleWave {
-    fn oscillator(&mut self, hz: f32, sample_rate: f32, sample_clock: &SampleClock) -> f32 {
-        let res = to_angular_vel_rate(hz, sample_rate, sample_clock)
-            .sin()
-            .asin()
-            * (2.0 / PI);
+    fn oscillator(&mut self, phase: f32) -> f32 {
+        let res = phase.sin().asin() * (2.0 / PI);
         res.my_clamp(-1.0, 1.0)
     }
 }
@@ -86,9 +79,7 @@ impl SinWave {
 }
 
 impl Oscillator for SinWave {
-    fn oscillator(&mut self, hz: f32, sample_rate: f32, sample_clock: &SampleClock) -> f32 {
-        to_angular_vel_rate(hz, sample_rate, sample_clock)
-            .sin()
-            .my_clamp(-1.0, 1.0)
+    fn oscillator(&mut self, phase: f32) -> f32 {
+        phase.sin().my_clamp(-1.0, 1.0)
     }
 }
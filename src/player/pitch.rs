@@ -0,0 +1,158 @@
+#[cfg(test)]
+use crate::player::sound::Letter;
+use crate::player::sound::{Note, Octave};
+use crate::player::tuning::Tuning;
+
+/// Keys of c_0 and g_9, the limits of the available octaves.
+const LOWEST_KEY: i32 = 12;
+const HIGHEST_KEY: i32 = 127;
+
+/// A pitch as a MIDI key number, where 60 is middle c (c_4),
+/// and an offset in cents from it.
+/// Microtonal scales come from the tuning the key is looked up in,
+/// the cents move a single sound, as bends and vibratos do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pitch {
+    key: i32,
+    cents: f32,
+    /// How the author wrote it, `cb_4` is the same key as `b_3`.
+    spelling: Option<Note>,
+    /// Spells the pitch with flats when it has no spelling of its own.
+    flats: bool,
+}
+
+impl Pitch {
+    /// `None` for a rest or a note outside of the available octaves.
+    pub fn new(note: Note, octave: Octave) -> Option<Self> {
+        let key = (octave.number() + 1) * 12 + note.class()?;
+        if !(LOWEST_KEY..=HIGHEST_KEY).contains(&key) {
+            return None;
+        }
+        Some(Self {
+            key,
+            cents: 0.0,
+            spelling: Some(note),
+            flats: matches!(note, Note::Spelled(_, a) if a < 0),
+        })
+    }
+
+    pub fn key(&self) -> i32 {
+        self.key
+    }
+
+    pub fn cents(&self) -> f32 {
+        self.cents
+    }
+
+    /// The same key moved by the given cents, keeping its spelling.
+    pub fn bent(&self, cents: f32) -> Self {
+        Self {
+            cents: self.cents + cents,
+            ..*self
+        }
+    }
+
+    pub fn note(&self) -> Note {
+        match self.spelling {
+            Some(n) => n,
+            None => Note::from_class(self.key, self.flats),
+        }
+    }
+
+    /// The octave the note is written in.
+    pub fn octave(&self) -> Octave {
+        let class = self.note().class().unwrap();
+        // the key range keeps the octave valid
+        Octave::from_number((self.key - class).div_euclid(12) - 1).unwrap()
+    }
+
+    /// `None` when the pitch would leave the available octaves.
+    /// Whole octaves keep the spelling, other intervals keep
+    /// to sharps or flats like the original.
+    pub fn transposed(&self, semitones: i32) -> Option<Self> {
+        let key = self.key + semitones;
+        if !(LOWEST_KEY..=HIGHEST_KEY).contains(&key) {
+            return None;
+        }
+        let spelling = match semitones % 12 {
+            0 => self.spelling,
+            _ => None,
+        };
+        Some(Self {
+            key,
+            cents: self.cents,
+            spelling,
+            flats: self.flats,
+        })
+    }
+
+    pub fn frequency(&self, tuning: &Tuning) -> f32 {
+        tuning.frequency(self.key) * (self.cents / 1200.0).exp2()
+    }
+}
+
+#[test]
+fn test_octaves_start_at_c() {
+    let middle_c = Pitch::new(Note::new(Letter::C, 0), Octave::Four).unwrap();
+    assert_eq!(60, middle_c.key());
+
+    let a = Pitch::new(Note::new(Letter::A, 0), Octave::Four).unwrap();
+    assert_eq!(69, a.key());
+    assert!((440.0 - a.frequency(&Tuning::equal())).abs() < 0.01);
+    // a semitone of cents reaches the next key, and stays with it when transposed
+    let a_sharp = a.bent(100.0);
+    assert!((466.16 - a_sharp.frequency(&Tuning::equal())).abs() < 0.01);
+    assert_eq!(Note::new(Letter::A, 0), a_sharp.note());
+    let up = a_sharp.transposed(1).unwrap();
+    assert!((100.0 - up.cents()).abs() < 1e-6);
+    assert!((493.88 - up.frequency(&Tuning::equal())).abs() < 0.01);
+
+    let b = Pitch::new(Note::new(Letter::B, 0), Octave::Three).unwrap();
+    assert!(matches!(b.transposed(1).unwrap().octave(), Octave::Four));
+
+    assert!(Pitch::new(Note::Space, Octave::Four).is_none());
+    assert!(Pitch::new(Note::new(Letter::A, 0), Octave::Nine).is_none());
+    assert!(Pitch::new(Note::new(Letter::C, -1), Octave::Zero).is_none());
+}
+
+#[test]
+fn test_enharmonic_spelling() {
+    let c_flat = Pitch::new(Note::new(Letter::C, -1), Octave::Four).unwrap();
+    let b = Pitch::new(Note::new(Letter::B, 0), Octave::Three).unwrap();
+    assert_eq!(b.key(), c_flat.key());
+
+    // the spelling and written octave are kept
+    assert_eq!(Note::new(Letter::C, -1), c_flat.note());
+    assert!(matches!(c_flat.octave(), Octave::Four));
+
+    // octaves keep it too, with the written octave moving along
+    let same = c_flat.transposed(0).unwrap();
+    assert_eq!(Note::new(Letter::C, -1), same.note());
+    assert!(matches!(same.octave(), Octave::Four));
+    let down = c_flat.transposed(-12).unwrap();
+    assert_eq!(Note::new(Letter::C, -1), down.note());
+    assert!(matches!(down.octave(), Octave::Three));
+    let e_double_sharp = Pitch::new(Note::new(Letter::E, 2), Octave::Two).unwrap();
+    assert_eq!(
+        Note::new(Letter::E, 2),
+        e_double_sharp.transposed(24).unwrap().note()
+    );
+
+    // other intervals spell the new key plainly
+    let e_double_flat = Pitch::new(Note::new(Letter::E, -2), Octave::Two).unwrap();
+    assert_eq!(
+        Note::new(Letter::E, -1),
+        e_double_flat.transposed(1).unwrap().note()
+    );
+
+    // transposing keeps to flats
+    let b_flat = Pitch::new(Note::new(Letter::B, -1), Octave::Three).unwrap();
+    let up = b_flat.transposed(1).unwrap().transposed(2).unwrap();
+    assert_eq!(Note::new(Letter::D, -1), up.note());
+    assert!(matches!(up.octave(), Octave::Four));
+    let f_sharp = Pitch::new(Note::new(Letter::F, 1), Octave::Three).unwrap();
+    assert_eq!(
+        Note::new(Letter::G, 1),
+        f_sharp.transposed(2).unwrap().note()
+    );
+}
@@ -1,4 +1,6 @@
 use crate::player::clamp::Clamp;
+use crate::player::frame::Frame;
+use crate::player::limiter::Limiter;
 use crate::player::song::Song;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{BuildStreamError, DefaultStreamConfigError, Device, PlayStreamError};
@@ -8,6 +10,12 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 
+// Level of a single voice on the master bus.
+// It is lowered as more voices play at once, leaving the peaks to the limiter.
+const HEADROOM: f32 = 0.25;
+// Time constant in seconds of the automatic headroom.
+const HEADROOM_SMOOTHING: f32 = 0.05;
+
 pub enum PlayErr {
     StreamErr(PlayStreamError),
     BuildStream(BuildStreamError),
@@ -34,6 +42,9 @@ impl Player {
         let (end_send, end_recv) = unbounded();
         let config = self.device.default_output_config()?;
         let tempo = song.start_tempo as f32;
+        let volume = song.master.volume;
+        let threshold_db = song.master.threshold_db;
+        let mut chain = song.master.chain;
         let mut instruments = song.instruments;
 
         thread::spawn(move || {
@@ -41,9 +52,20 @@ impl Player {
             let finished_clone = Arc::clone(&finished);
             let mut finished_vec = vec![false; instruments.len()];
             let channels = config.channels() as usize;
-            let volume = 0.5;
             let sample_rate = config.sample_rate().0 as f32;
             let instr_len = instruments.len();
+            let smoothing = 1.0 - (-1.0 / (HEADROOM_SMOOTHING * sample_rate)).exp();
+            let mut headroom = HEADROOM;
+            let mut limiter = Limiter::new(threshold_db, sample_rate);
+            // the master bus counts as finished once its effects have rung out
+            // and the limiter has let out what it was holding back
+            let tail = chain
+                .iter()
+                .map(|p| p.tail(sample_rate, tempo))
+                .fold(0.0, f32::max)
+                + limiter.tail(sample_rate);
+            let mut master_tail = tail * sample_rate;
+            let mut master_finished = false;
 
             let err_fn = |err| warn!("an error occurred on stream: {}", err);
 
@@ -51,7 +73,8 @@ impl Player {
                 &config.into(),
                 move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
                     for frame in data.chunks_mut(channels) {
-                        let mut sine_amp = 0.0;
+                        let mut mix = Frame::default();
+                        let mut voices = 0;
 
                         for (i, instrument) in instruments.iter_mut().enumerate() {
                             if !finished_vec[i] && instrument.is_finished() {
@@ -60,14 +83,28 @@ impl Player {
                                 continue;
                             }
 
-                            sine_amp += instrument.next_freq(sample_rate, tempo);
+                            mix += instrument.next_frame(sample_rate, tempo);
+                            voices += instrument.voices();
                         }
 
-                        let next_freq = (sine_amp * volume * 0.1).my_clamp(-1.0, 1.0);
+                        let target = HEADROOM / (voices.max(1) as f32).sqrt();
+                        headroom += (target - headroom) * smoothing;
+
+                        let mut mix = mix * headroom;
+                        for processor in chain.iter_mut() {
+                            mix = processor.process(mix, sample_rate, tempo);
+                        }
 
-                        for sample in frame.iter_mut() {
-                            *sample = next_freq;
+                        if !master_finished && finished_vec.iter().all(|f| *f) {
+                            if master_tail <= 0.0 {
+                                master_finished = true;
+                                finished_clone.fetch_add(1, Ordering::SeqCst);
+                            }
+                            master_tail -= 1.0;
                         }
+
+                        let mix = limiter.process(mix * volume);
+                        write_frame(frame, mix);
                     }
                 },
                 err_fn,
@@ -89,7 +126,7 @@ impl Player {
                         warn!("could not send stream error {}", ee);
                     }
                 }
-                if finished.load(Ordering::SeqCst) == instr_len {
+                if finished.load(Ordering::SeqCst) == instr_len + 1 {
                     if let Err(ee) = end_send.send(PlayErr::EndOfSong) {
                         warn!("could not send end of stream {}", ee);
                     }
@@ -100,3 +137,20 @@ impl Player {
         Ok(end_recv)
     }
 }
+
+fn write_frame(out: &mut [f32], frame: Frame) {
+    let left = frame.left.my_clamp(-1.0, 1.0);
+    let right = frame.right.my_clamp(-1.0, 1.0);
+
+    match out {
+        [mono] => *mono = frame.mono().my_clamp(-1.0, 1.0),
+        [l, r, rest @ ..] => {
+            *l = left;
+            *r = right;
+            for sample in rest.iter_mut() {
+                *sample = (left + right) * 0.5;
+            }
+        }
+        [] => {}
+    }
+}
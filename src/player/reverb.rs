@@ -0,0 +1,132 @@
+use crate::player::effect::Processor;
+use crate::player::frame::Frame;
+
+// Freeverb tuning, given in samples at 44.1kHz
+const TUNING_RATE: f32 = 44100.0;
+const COMBS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASSES: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const FIXED_GAIN: f32 = 0.015;
+const SCALE_WET: f32 = 3.0;
+const MAX_TAIL: f32 = 10.0;
+
+#[derive(Debug, Clone)]
+struct Comb {
+    buf: Vec<f32>,
+    pos: usize,
+    store: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len.max(1)],
+            pos: 0,
+            store: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damp: f32) -> f32 {
+        let out = self.buf[self.pos];
+        self.store = out * (1.0 - damp) + self.store * damp;
+        self.buf[self.pos] = input + self.store * feedback;
+        self.pos = (self.pos + 1) % self.buf.len();
+        out
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AllPass {
+    buf: Vec<f32>,
+    pos: usize,
+}
+
+impl AllPass {
+    fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buf[self.pos];
+        self.buf[self.pos] = input + buffered * 0.5;
+        self.pos = (self.pos + 1) % self.buf.len();
+        buffered - input
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Channel {
+    combs: Vec<Comb>,
+    allpasses: Vec<AllPass>,
+}
+
+impl Channel {
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let scale = |len: usize| ((len + spread) as f32 * sample_rate / TUNING_RATE) as usize;
+        Self {
+            combs: COMBS.iter().map(|&l| Comb::new(scale(l))).collect(),
+            allpasses: ALLPASSES.iter().map(|&l| AllPass::new(scale(l))).collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damp: f32) -> f32 {
+        let mut out = 0.0;
+        for comb in self.combs.iter_mut() {
+            out += comb.process(input, feedback, damp);
+        }
+        for allpass in self.allpasses.iter_mut() {
+            out = allpass.process(out);
+        }
+        out
+    }
+}
+
+/// Schroeder/Moorer reverb as tuned by Freeverb.
+#[derive(Debug, Clone)]
+pub struct Reverb {
+    feedback: f32,
+    damp: f32,
+    wet: f32,
+    // created on the first sample, once the sample rate is known
+    channels: Option<(Channel, Channel)>,
+}
+
+impl Reverb {
+    /// All parameters go from 0.0 to 1.0.
+    pub fn new(room_size: f32, damping: f32, wet: f32) -> Self {
+        Self {
+            feedback: 0.7 + room_size * 0.28,
+            damp: damping * 0.4,
+            wet,
+            channels: None,
+        }
+    }
+}
+
+impl Processor for Reverb {
+    fn process(&mut self, frame: Frame, sample_rate: f32, _beat_per_min: f32) -> Frame {
+        let (left, right) = self.channels.get_or_insert_with(|| {
+            (
+                Channel::new(sample_rate, 0),
+                Channel::new(sample_rate, STEREO_SPREAD),
+            )
+        });
+
+        let input = (frame.left + frame.right) * FIXED_GAIN;
+        let wet = Frame::new(
+            left.process(input, self.feedback, self.damp),
+            right.process(input, self.feedback, self.damp),
+        );
+
+        frame * (1.0 - self.wet) + wet * (self.wet * SCALE_WET)
+    }
+
+    fn tail(&self, _sample_rate: f32, _beat_per_min: f32) -> f32 {
+        // time for the longest comb to decay by 60dB
+        let longest = COMBS[COMBS.len() - 1] as f32 / TUNING_RATE;
+        (longest * (0.001_f32).ln() / self.feedback.ln()).min(MAX_TAIL)
+    }
+}
@@ -0,0 +1,158 @@
+#[cfg(test)]
+use crate::player::sound::Letter;
+use crate::player::sound::{Note, Octave, Sound};
+use crate::player::tempo::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Scale {
+    root: Note,
+    /// Octaves the root was moved by transposing, played above
+    /// (or below) the octave the scale is asked for.
+    octaves: i32,
+    /// Semitones of every note above the root, starting with 0.
+    offsets: Vec<i32>,
+}
+
+impl Scale {
+    pub fn from_name(root: Note, name: &str) -> Option<Self> {
+        let offsets: &[i32] = match name {
+            "major" | "ionian" => &[0, 2, 4, 5, 7, 9, 11],
+            "minor" | "aeolian" => &[0, 2, 3, 5, 7, 8, 10],
+            "harmonic minor" => &[0, 2, 3, 5, 7, 8, 11],
+            "melodic minor" => &[0, 2, 3, 5, 7, 9, 11],
+            "dorian" => &[0, 2, 3, 5, 7, 9, 10],
+            "phrygian" => &[0, 1, 3, 5, 7, 8, 10],
+            "lydian" => &[0, 2, 4, 6, 7, 9, 11],
+            "mixolydian" => &[0, 2, 4, 5, 7, 9, 10],
+            "locrian" => &[0, 1, 3, 5, 6, 8, 10],
+            "major pentatonic" => &[0, 2, 4, 7, 9],
+            "minor pentatonic" => &[0, 3, 5, 7, 10],
+            "blues" => &[0, 3, 5, 6, 7, 10],
+            "chromatic" => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            _ => return None,
+        };
+        Some(Self {
+            root,
+            octaves: 0,
+            offsets: offsets.to_vec(),
+        })
+    }
+
+    /// A scale from the semitones between its consecutive notes,
+    /// `None` if they are not positive or span more than an octave.
+    pub fn from_steps(root: Note, steps: &[i32]) -> Option<Self> {
+        let mut offsets = vec![0];
+        let mut total = 0;
+        for step in steps {
+            if *step <= 0 {
+                return None;
+            }
+            total += step;
+            offsets.push(total);
+        }
+        if total > 12 {
+            return None;
+        }
+        // a last step back to the root only closes the octave
+        if total == 12 {
+            offsets.pop();
+        }
+        Some(Self {
+            root,
+            octaves: 0,
+            offsets,
+        })
+    }
+
+    /// The note of the 1-based degree, degrees past the last
+    /// note of the scale continue in the octaves above.
+    pub fn degree(&self, degree: usize, octave: Octave, duration: Duration) -> Option<Sound> {
+        if degree == 0 {
+            return None;
+        }
+        let len = self.offsets.len();
+        let octaves = ((degree - 1) / len) as i32;
+        let semitones = self.offsets[(degree - 1) % len] + octaves * 12;
+        let octave = Octave::from_number(octave.number() + self.octaves)?;
+        Sound::new(self.root, octave, duration).transposed(semitones)
+    }
+
+    /// One octave of the scale going up from the root.
+    pub fn sounds(&self, octave: Octave, duration: Duration) -> Option<Vec<Sound>> {
+        (1..=self.offsets.len())
+            .map(|degree| self.degree(degree, octave.clone(), duration.clone()))
+            .collect()
+    }
+
+    /// The same scale with its root moved by the semitones,
+    /// `None` when a root in the 4th octave would leave the available octaves.
+    pub fn transposed(&self, semitones: i32) -> Option<Self> {
+        let octave = Octave::from_number(Octave::Four.number() + self.octaves)?;
+        let root = Sound::new(self.root, octave, Duration::note(4)).transposed(semitones)?;
+        Some(Self {
+            root: root.note(),
+            octaves: root.octave().number() - Octave::Four.number(),
+            offsets: self.offsets.clone(),
+        })
+    }
+}
+
+#[test]
+fn test_scale_degree() {
+    let dorian = Scale::from_name(Note::new(Letter::D, 0), "dorian").unwrap();
+    let sixth = dorian.degree(6, Octave::Four, Duration::note(4)).unwrap();
+    assert_eq!(Note::new(Letter::B, 0), sixth.note());
+
+    // the octave of the root is reached again on the 8th degree
+    let eighth = dorian.degree(8, Octave::Four, Duration::note(4)).unwrap();
+    assert_eq!(Note::new(Letter::D, 0), eighth.note());
+    assert!(matches!(eighth.octave(), Octave::Five));
+
+    let custom = Scale::from_steps(Note::new(Letter::C, 0), &[2, 2, 1, 2, 2, 2, 1]).unwrap();
+    let octave = custom.degree(8, Octave::Four, Duration::note(4)).unwrap();
+    assert_eq!(Note::new(Letter::C, 0), octave.note());
+    assert!(matches!(octave.octave(), Octave::Five));
+    assert!(Scale::from_steps(Note::new(Letter::C, 0), &[7, 7]).is_none());
+    assert!(Scale::from_steps(Note::new(Letter::C, 0), &[2, 0, 3]).is_none());
+}
+
+#[test]
+fn test_scale_sounds() {
+    let pentatonic = Scale::from_name(Note::new(Letter::A, 0), "minor pentatonic").unwrap();
+    let notes: Vec<Note> = pentatonic
+        .transposed(2)
+        .unwrap()
+        .sounds(Octave::Four, Duration::note(8))
+        .unwrap()
+        .iter()
+        .map(|s| s.note())
+        .collect();
+    let expected = [
+        Note::new(Letter::B, 0),
+        Note::new(Letter::D, 0),
+        Note::new(Letter::E, 0),
+        Note::new(Letter::F, 1),
+        Note::new(Letter::A, 0),
+    ];
+    assert_eq!(expected.to_vec(), notes);
+}
+
+#[test]
+fn test_scale_transposed_across_c() {
+    let major = Scale::from_name(Note::new(Letter::A, 0), "major").unwrap();
+    let up = major.transposed(3).unwrap();
+    let root = up.degree(1, Octave::Four, Duration::note(4)).unwrap();
+    assert_eq!(Note::new(Letter::C, 0), root.note());
+    assert!(matches!(root.octave(), Octave::Five));
+
+    // a whole octave moves every note, and back down again
+    let octave = major.transposed(12).unwrap();
+    let root = octave.degree(1, Octave::Four, Duration::note(4)).unwrap();
+    assert!(matches!(root.octave(), Octave::Five));
+    let back = octave.transposed(-12).unwrap();
+    let root = back.degree(1, Octave::Four, Duration::note(4)).unwrap();
+    assert_eq!(Note::new(Letter::A, 0), root.note());
+    assert!(matches!(root.octave(), Octave::Four));
+
+    assert!(major.transposed(90).is_none());
+}
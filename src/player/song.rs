@@ -1,4 +1,5 @@
 use crate::player::instrument::{InstrumentBox, Instruments};
+use crate::player::mixer::Master;
 use crate::player::tempo::{Tempo, TempoErr};
 
 pub struct Song {
@@ -6,6 +7,7 @@ pub struct Song {
     pub tempo_changes: Vec<Tempo>,
     tempo_cum: u32,
     pub instruments: Instruments,
+    pub master: Master,
 }
 
 #[allow(dead_code)]
@@ -16,6 +18,7 @@ impl Song {
             tempo_changes: vec![],
             tempo_cum: 0,
             instruments: vec![],
+            master: Master::new(),
         }
     }
 
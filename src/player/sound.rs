@@ -1,8 +1,11 @@
 use crate::player::effect::EffectBox;
+use crate::player::pitch::Pitch;
 use crate::player::tempo::{Duration, SampleClock};
 
-const A: f32 = 440.0;
+/// Amplitude boost of accented notes, about 4 dB.
+const ACCENT: f32 = 1.6;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct Envelope {
     attack_time: f32,
@@ -54,8 +57,19 @@ impl Envelope {
 
         amplitude
     }
+
+    /// Amplitude of a note that continues the previous one without an attack,
+    /// fading out over the last `release` samples.
+    pub fn get_tied_amplitude(&self, sample_clock: &SampleClock, release: f32) -> f32 {
+        let left = sample_clock.get_dur() - sample_clock.get_clock();
+        if release > 0.0 && left < release {
+            return self.sustain_amplitude * (left / release).max(0.0);
+        }
+        self.sustain_amplitude
+    }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct Frequency {
     pub frequency: f32,
@@ -64,60 +78,263 @@ pub struct Frequency {
 
 #[derive(Debug, Clone)]
 pub struct Sound {
-    pub(crate) note: Note,
-    pub(crate) octave: Octave,
+    /// `None` for a rest.
+    pub(crate) pitch: Option<Pitch>,
     pub(crate) duration: Duration,
     pub(crate) effects: Option<Vec<EffectBox>>,
+    /// Slides from the previous note instead of jumping to its pitch.
+    pub(crate) legato: bool,
+    /// Starts with its own attack rather than continuing the previous note.
+    pub(crate) attack: bool,
+    /// From 1 to 127, `None` plays at full level.
+    pub(crate) velocity: Option<u8>,
+    /// Part of its duration the note sounds for, the next note still
+    /// starts after the whole duration.
+    pub(crate) sounding: f32,
+    /// Stretches both the note and the wait for the next one.
+    pub(crate) hold: f32,
+    pub(crate) accent: bool,
 }
 
 impl Sound {
     pub fn new(note: Note, octave: Octave, duration: Duration) -> Self {
         Self {
-            note,
-            octave,
+            pitch: Pitch::new(note, octave),
             duration,
             effects: None,
+            legato: false,
+            attack: true,
+            velocity: None,
+            sounding: 1.0,
+            hold: 1.0,
+            accent: false,
         }
     }
-}
 
-impl Note {
-    pub fn frequency(self, octave: Octave) -> f32 {
-        if let Note::Space = self {
-            return 0.0;
+    /// Amplitude factor of the velocity and accent.
+    pub fn gain(&self) -> f32 {
+        let gain = match self.velocity {
+            // squared, so the steps sound even
+            Some(v) => (v as f32 / 127.0).powi(2),
+            None => 1.0,
+        };
+        match self.accent {
+            true => gain * ACCENT,
+            false => gain,
+        }
+    }
+
+    pub fn push_effect(&mut self, effect: EffectBox) {
+        match self.effects.as_mut() {
+            Some(e_box) => e_box.push(effect),
+            None => self.effects = Some(vec![effect]),
+        }
+    }
+
+    pub fn note(&self) -> Note {
+        match self.pitch {
+            Some(p) => p.note(),
+            None => Note::Space,
+        }
+    }
+
+    /// Rests are written in the 4th octave.
+    pub fn octave(&self) -> Octave {
+        match self.pitch {
+            Some(p) => p.octave(),
+            None => Octave::Four,
         }
-        let float = (self as isize - 1 + octave as isize) as f32 / 12.0;
-        A * float.exp2()
+    }
+
+    /// The same sound moved by the given semitones,
+    /// `None` when it would leave the available octaves.
+    pub fn transposed(&self, semitones: i32) -> Option<Sound> {
+        let mut sound = self.clone();
+        if let Some(p) = self.pitch {
+            sound.pitch = Some(p.transposed(semitones)?);
+        }
+        Some(sound)
     }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub enum Note {
-    Space,
-    A,
-    ASharp,
-    B,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Letter {
     C,
-    CSharp,
     D,
-    DSharp,
     E,
     F,
-    FSharp,
     G,
-    GSharp,
+    A,
+    B,
 }
 
+impl Letter {
+    /// Semitones above c.
+    fn class(self) -> i32 {
+        match self {
+            Letter::C => 0,
+            Letter::D => 2,
+            Letter::E => 4,
+            Letter::F => 5,
+            Letter::G => 7,
+            Letter::A => 9,
+            Letter::B => 11,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Letter::C => "c",
+            Letter::D => "d",
+            Letter::E => "e",
+            Letter::F => "f",
+            Letter::G => "g",
+            Letter::A => "a",
+            Letter::B => "b",
+        }
+    }
+}
+
+/// A note as the author spelled it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Note {
+    Space,
+    /// A letter and its accidentals, sharps are positive and flats negative.
+    Spelled(Letter, i32),
+}
+
+/// Octaves in scientific pitch notation, they start at c.
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Octave {
-    One = -36,
-    Two = -24,
-    Three = -12,
-    Four = 0,
-    Five = 12,
-    Six = 24,
-    Seven = 36,
-    Eight = 48,
+    Zero = 0,
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+}
+
+impl Note {
+    pub fn new(letter: Letter, accidentals: i32) -> Self {
+        Note::Spelled(letter, accidentals)
+    }
+
+    /// Semitones above the c of the octave it is written in, `None` for a rest.
+    /// Can be outside of 0..12, `cb` is -1 and `b#` is 12.
+    pub fn class(&self) -> Option<i32> {
+        match self {
+            Note::Space => None,
+            Note::Spelled(letter, accidentals) => Some(letter.class() + accidentals),
+        }
+    }
+
+    /// The usual spelling of a pitch class, with sharps or flats.
+    pub(crate) fn from_class(class: i32, flats: bool) -> Note {
+        const SHARPS: [(Letter, i32); 12] = [
+            (Letter::C, 0),
+            (Letter::C, 1),
+            (Letter::D, 0),
+            (Letter::D, 1),
+            (Letter::E, 0),
+            (Letter::F, 0),
+            (Letter::F, 1),
+            (Letter::G, 0),
+            (Letter::G, 1),
+            (Letter::A, 0),
+            (Letter::A, 1),
+            (Letter::B, 0),
+        ];
+        const FLATS: [(Letter, i32); 12] = [
+            (Letter::C, 0),
+            (Letter::D, -1),
+            (Letter::D, 0),
+            (Letter::E, -1),
+            (Letter::E, 0),
+            (Letter::F, 0),
+            (Letter::G, -1),
+            (Letter::G, 0),
+            (Letter::A, -1),
+            (Letter::A, 0),
+            (Letter::B, -1),
+            (Letter::B, 0),
+        ];
+        let spellings = match flats {
+            true => &FLATS,
+            false => &SHARPS,
+        };
+        let (letter, accidentals) = spellings[class.rem_euclid(12) as usize];
+        Note::Spelled(letter, accidentals)
+    }
+
+    /// The name used in scripts, like `c#`, `eb` or `x` for a rest.
+    pub fn name(&self) -> String {
+        match self {
+            Note::Space => "x".to_string(),
+            Note::Spelled(letter, accidentals) => {
+                let accidental = match *accidentals >= 0 {
+                    true => "#",
+                    false => "b",
+                };
+                format!(
+                    "{}{}",
+                    letter.name(),
+                    accidental.repeat(accidentals.unsigned_abs() as usize)
+                )
+            }
+        }
+    }
+}
+
+impl Octave {
+    const ALL: [Octave; 10] = [
+        Octave::Zero,
+        Octave::One,
+        Octave::Two,
+        Octave::Three,
+        Octave::Four,
+        Octave::Five,
+        Octave::Six,
+        Octave::Seven,
+        Octave::Eight,
+        Octave::Nine,
+    ];
+
+    pub fn number(&self) -> i32 {
+        self.clone() as i32
+    }
+
+    pub(crate) fn from_number(number: i32) -> Option<Octave> {
+        Self::ALL.iter().find(|o| o.number() == number).cloned()
+    }
+}
+
+#[test]
+fn test_transposed_rolls_over_octaves() {
+    let sound = Sound::new(Note::new(Letter::A, 0), Octave::Four, Duration::note(4));
+
+    let up = sound.transposed(3).unwrap();
+    assert_eq!(Note::new(Letter::C, 0), up.note());
+    assert!(matches!(up.octave(), Octave::Five));
+
+    let down = sound.transposed(-24).unwrap();
+    assert_eq!(Note::new(Letter::A, 0), down.note());
+    assert!(matches!(down.octave(), Octave::Two));
+
+    assert!(sound.transposed(60).is_none());
+    assert!(sound.transposed(-60).is_none());
+
+    let rest = Sound::new(Note::Space, Octave::Four, Duration::note(4));
+    assert!(rest.transposed(60).unwrap().pitch.is_none());
+}
+
+#[test]
+fn test_note_names() {
+    assert_eq!("eb", Note::new(Letter::E, -1).name());
+    assert_eq!("f##", Note::new(Letter::F, 2).name());
+    assert_eq!("x", Note::Space.name());
 }
@@ -1,5 +1,6 @@
 const SEC_PER_MIN: f32 = 60.0;
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum TempoErr {
     InvalidOp {
@@ -10,6 +11,7 @@ pub enum TempoErr {
     },
 }
 
+#[allow(dead_code)]
 pub struct Tempo {
     pub value: u32,
     pub from_beat: u32,
@@ -22,44 +24,105 @@ pub struct Rates<'a> {
 }
 
 pub fn calc_duration(rates: Rates) -> f32 {
-    let rate_per_beat: f32 = rates.sample_rate / (rates.beat_per_min / SEC_PER_MIN);
-    rate_per_beat * rates.duration.to_beats()
+    // a beat is a quarter note
+    let samples = rates.sample_rate as f64 * SEC_PER_MIN as f64 * 4.0 * rates.duration.num as f64;
+    (samples / (rates.beat_per_min as f64 * rates.duration.den as f64)) as f32
 }
 
-#[derive(Debug, Clone)]
-pub enum Duration {
-    Whole,
-    HalfDotted,
-    Half,
-    QuarterDotted,
-    Quarter,
-    EightDotted,
-    Eight,
-    SixteenthDotted,
-    Sixteenth,
-    ThirtySecondDotted,
-    ThirtySecond,
-    SixtyFourthDotted,
-    SixtyFourth,
+/// A note value as an exact fraction of a whole note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duration {
+    num: u32,
+    den: u32,
 }
 
 impl Duration {
-    pub fn to_beats(&self) -> f32 {
-        match self {
-            Self::Whole => 4.0,
-            Self::HalfDotted => 3.0,
-            Self::Half => 2.0,
-            Self::QuarterDotted => 1.5,
-            Self::Quarter => 1.0,
-            Self::EightDotted => 0.75,
-            Self::Eight => 0.5,
-            Self::SixteenthDotted => 0.375,
-            Self::Sixteenth => 0.25,
-            Self::ThirtySecondDotted => 0.1875,
-            Self::ThirtySecond => 0.125,
-            Self::SixtyFourthDotted => 0.09375,
-            Self::SixtyFourth => 0.0625,
+    pub fn new(num: u32, den: u32) -> Self {
+        let gcd = gcd(num, den);
+        Self {
+            num: num / gcd,
+            den: den / gcd,
+        }
+    }
+
+    /// A plain note value, 4 is a quarter note.
+    pub fn note(value: u32) -> Self {
+        Self::new(1, value)
+    }
+
+    /// Every dot adds half of the previous one.
+    pub fn dotted(&self, dots: u32) -> Self {
+        let extra = 2_u32.pow(dots);
+        Self::new(self.num * (2 * extra - 1), self.den * extra)
+    }
+
+    /// `notes` of these in the time of `in_time_of` of them.
+    pub fn tuplet(&self, notes: u32, in_time_of: u32) -> Self {
+        Self::new(self.num * in_time_of, self.den * notes)
+    }
+
+    /// `count` of these one after the other, `None` when too long to count.
+    pub fn times(&self, count: u32) -> Option<Self> {
+        Some(Self::new(self.num.checked_mul(count)?, self.den))
+    }
+
+    pub fn tied(&self, other: &Duration) -> Self {
+        Self::new(
+            self.num * other.den + other.num * self.den,
+            self.den * other.den,
+        )
+    }
+
+    /// Parses the name used in scripts without its `d`: a note value from 1 to 128,
+    /// followed by `t`, `q` or `s` for triplets, quintuplets and septuplets,
+    /// up to two dots written as `*`, and tied values separated by `~`.
+    /// `4`, `8t`, `4**`, `2~8`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut tied = name.split('~');
+        let mut duration = Self::from_single_name(tied.next()?)?;
+        for next in tied {
+            duration = duration.tied(&Self::from_single_name(next)?);
         }
+        Some(duration)
+    }
+
+    fn from_single_name(name: &str) -> Option<Self> {
+        let digits = name.chars().take_while(|c| c.is_ascii_digit()).count();
+        let value = name[..digits].parse::<u32>().ok()?;
+        if !value.is_power_of_two() || value > 128 {
+            return None;
+        }
+        let mut duration = Self::note(value);
+
+        let mut rest = &name[digits..];
+        let tuplet = match rest.chars().next() {
+            Some('t') => Some((3, 2)),
+            Some('q') => Some((5, 4)),
+            Some('s') => Some((7, 4)),
+            _ => None,
+        };
+        if let Some((notes, in_time_of)) = tuplet {
+            duration = duration.tuplet(notes, in_time_of);
+            rest = &rest[1..];
+        }
+
+        match rest {
+            "" => Some(duration),
+            "*" => Some(duration.dotted(1)),
+            "**" => Some(duration.dotted(2)),
+            _ => None,
+        }
+    }
+
+    pub fn to_beats(&self) -> f32 {
+        4.0 * self.num as f32 / self.den as f32
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    match b {
+        0 => a.max(1),
+        _ => gcd(b, a % b),
     }
 }
 
@@ -95,3 +158,32 @@ impl SampleClock {
         self.ended
     }
 }
+
+#[test]
+fn test_duration_names() {
+    let tests = vec![
+        ("4", 1.0),
+        ("8*", 0.75),
+        ("4**", 1.75),
+        ("8t", 1.0 / 3.0),
+        ("16q", 0.2),
+        ("2~8", 2.5),
+        ("4t~4t~4t", 2.0),
+        ("64*", 0.09375),
+    ];
+    for (name, beats) in tests {
+        let duration = Duration::from_name(name).unwrap();
+        assert!((beats - duration.to_beats()).abs() < 1e-6, "{}", name);
+    }
+
+    // three triplet eighths make a quarter exactly
+    let triplet = Duration::from_name("8t").unwrap();
+    assert_eq!(Duration::note(4), triplet.tied(&triplet).tied(&triplet));
+    // two bars of 6/8
+    assert_eq!(Duration::new(3, 2), Duration::note(8).times(12).unwrap());
+    assert!(Duration::new(3, 8).times(u32::MAX).is_none());
+
+    for name in &["3", "4***", "8x", "~4", "256", ""] {
+        assert!(Duration::from_name(name).is_none(), "{}", name);
+    }
+}
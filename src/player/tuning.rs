@@ -0,0 +1,323 @@
+/// Key number of the a above middle c, the usual reference pitch.
+pub const A4_KEY: i32 = 69;
+const A4: f32 = 440.0;
+const MIDDLE_C_KEY: i32 = 60;
+
+/// Maps key numbers to frequencies.
+///
+/// The scale is a list of degrees in cents repeating every period,
+/// the keyboard mapping places the degrees on the keys, as in the Scala formats.
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    /// Cents of every degree above the first, starting with 0.
+    degrees: Vec<f32>,
+    /// Cents of the interval the degrees repeat at, usually an octave.
+    period: f32,
+    map: KeyMap,
+}
+
+#[derive(Debug, Clone)]
+struct KeyMap {
+    first: i32,
+    last: i32,
+    /// Key of the first degree of the scale.
+    middle: i32,
+    reference: i32,
+    frequency: f32,
+    /// Degree reached when the mapping repeats.
+    octave_degree: i32,
+    /// Degree of every key of the pattern, `None` for unmapped keys.
+    /// An empty pattern maps the degrees to consecutive keys.
+    keys: Vec<Option<i32>>,
+}
+
+impl KeyMap {
+    fn linear(middle: i32) -> Self {
+        Self {
+            first: i32::MIN,
+            last: i32::MAX,
+            middle,
+            reference: A4_KEY,
+            frequency: A4,
+            octave_degree: 0,
+            keys: vec![],
+        }
+    }
+}
+
+impl Tuning {
+    pub fn equal() -> Self {
+        Self::from_cents(
+            (0..12).map(|i| i as f32 * 100.0).collect(),
+            1200.0,
+            MIDDLE_C_KEY,
+        )
+    }
+
+    /// One of the historical temperaments, with its first degree on the root.
+    pub fn from_name(name: &str, root: i32) -> Option<Self> {
+        let ratios: [(u32, u32); 12] = match name {
+            "equal" => return Some(Self::equal()),
+            "just" => [
+                (1, 1),
+                (16, 15),
+                (9, 8),
+                (6, 5),
+                (5, 4),
+                (4, 3),
+                (45, 32),
+                (3, 2),
+                (8, 5),
+                (5, 3),
+                (9, 5),
+                (15, 8),
+            ],
+            "pythagorean" => [
+                (1, 1),
+                (256, 243),
+                (9, 8),
+                (32, 27),
+                (81, 64),
+                (4, 3),
+                (729, 512),
+                (3, 2),
+                (128, 81),
+                (27, 16),
+                (16, 9),
+                (243, 128),
+            ],
+            "meantone" => {
+                // quarter comma, every fifth is a quarter of the syntonic comma narrower
+                let fifth = 1200.0 * 1.5_f32.log2() - 1200.0 * (81.0_f32 / 80.0).log2() / 4.0;
+                let degrees = (0..12)
+                    .map(|i| {
+                        (fifth * [0, 7, 2, 9, 4, 11, 6, 1, 8, 3, 10, 5][i] as f32)
+                            .rem_euclid(1200.0)
+                    })
+                    .collect();
+                return Some(Self::from_cents(degrees, 1200.0, root));
+            }
+            "werckmeister" => {
+                let degrees = vec![
+                    0.0, 90.225, 192.18, 294.135, 390.225, 498.045, 588.27, 696.09, 792.18, 888.27,
+                    996.09, 1092.18,
+                ];
+                return Some(Self::from_cents(degrees, 1200.0, root));
+            }
+            _ => return None,
+        };
+        let degrees = ratios.iter().map(|(n, d)| ratio_to_cents(*n, *d)).collect();
+        Some(Self::from_cents(degrees, 1200.0, root))
+    }
+
+    fn from_cents(degrees: Vec<f32>, period: f32, root: i32) -> Self {
+        // the first degree goes on the root closest to middle c
+        let middle = MIDDLE_C_KEY + (root - MIDDLE_C_KEY).rem_euclid(12);
+        Self {
+            degrees,
+            period,
+            map: KeyMap::linear(middle),
+        }
+    }
+
+    /// Reads a Scala scale file and optionally a keyboard mapping file.
+    /// Without a mapping the first degree is on middle c.
+    pub fn from_scala(scl: &str, kbm: Option<&str>) -> Result<Self, String> {
+        let mut lines = scl
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('!'))
+            .skip(1);
+
+        let count = match lines.next().map(|l| first_word(l).parse::<usize>()) {
+            Some(Ok(c)) if c > 0 => c,
+            _ => return Err("expected the number of notes on the second line".to_string()),
+        };
+
+        let mut degrees = vec![0.0];
+        for line in lines.take(count) {
+            degrees.push(parse_pitch(first_word(line))?);
+        }
+        if degrees.len() != count + 1 {
+            return Err(format!(
+                "expected {} notes, found {}",
+                count,
+                degrees.len() - 1
+            ));
+        }
+        let period = degrees.pop().unwrap();
+        if period <= 0.0 {
+            return Err("the last note must be above the first".to_string());
+        }
+
+        let mut tuning = Self {
+            degrees,
+            period,
+            map: KeyMap::linear(MIDDLE_C_KEY),
+        };
+        if let Some(kbm) = kbm {
+            tuning.map = parse_kbm(kbm)?;
+        }
+        Ok(tuning)
+    }
+
+    /// Tunes the a above middle c to the given frequency.
+    pub fn set_reference(&mut self, frequency: f32) {
+        self.map.reference = A4_KEY;
+        self.map.frequency = frequency;
+    }
+
+    /// Keeps the reference pitch of another tuning.
+    pub fn reference_from(&mut self, other: &Tuning) {
+        self.map.reference = other.map.reference;
+        self.map.frequency = other.map.frequency;
+    }
+
+    pub fn frequency(&self, key: i32) -> f32 {
+        if key < self.map.first || key > self.map.last {
+            return 0.0;
+        }
+        let cents = match self.cents(key) {
+            Some(c) => c,
+            None => return 0.0,
+        };
+        let reference = self.cents(self.map.reference).unwrap_or(0.0);
+        self.map.frequency * ((cents - reference) / 1200.0).exp2()
+    }
+
+    fn cents(&self, key: i32) -> Option<f32> {
+        let offset = key - self.map.middle;
+        let degree = match self.map.keys.len() as i32 {
+            0 => offset,
+            size => {
+                self.map.keys[offset.rem_euclid(size) as usize]?
+                    + offset.div_euclid(size) * self.map.octave_degree
+            }
+        };
+        let size = self.degrees.len() as i32;
+        Some(
+            self.degrees[degree.rem_euclid(size) as usize]
+                + self.period * degree.div_euclid(size) as f32,
+        )
+    }
+}
+
+fn ratio_to_cents(numerator: u32, denominator: u32) -> f32 {
+    1200.0 * (numerator as f32 / denominator as f32).log2()
+}
+
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+// Pitches with a dot are in cents, the others are ratios like 3/2 or 2.
+fn parse_pitch(pitch: &str) -> Result<f32, String> {
+    let invalid = || format!("invalid pitch `{}`", pitch);
+    if pitch.contains('.') {
+        return pitch.parse::<f32>().map_err(|_| invalid());
+    }
+    let mut parts = pitch.splitn(2, '/');
+    let numerator = parts
+        .next()
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|_| invalid())?;
+    let denominator = match parts.next() {
+        Some(d) => d.parse::<u32>().map_err(|_| invalid())?,
+        None => 1,
+    };
+    if numerator == 0 || denominator == 0 {
+        return Err(invalid());
+    }
+    Ok(ratio_to_cents(numerator, denominator))
+}
+
+fn parse_kbm(kbm: &str) -> Result<KeyMap, String> {
+    let mut values = kbm
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('!') && !l.trim().is_empty())
+        .map(first_word);
+
+    let mut header = [0.0_f32; 7];
+    let names = [
+        "map size",
+        "first note",
+        "last note",
+        "middle note",
+        "reference note",
+        "reference frequency",
+        "octave degree",
+    ];
+    for (value, name) in header.iter_mut().zip(names.iter()) {
+        *value = match values.next().map(|v| v.parse::<f32>()) {
+            Some(Ok(v)) => v,
+            _ => return Err(format!("invalid or missing {} in keyboard mapping", name)),
+        };
+    }
+
+    let size = header[0] as usize;
+    let mut keys = Vec::with_capacity(size);
+    for value in values.take(size) {
+        match value {
+            "x" => keys.push(None),
+            v => match v.parse::<i32>() {
+                Ok(d) => keys.push(Some(d)),
+                Err(_) => return Err(format!("invalid degree `{}` in keyboard mapping", v)),
+            },
+        }
+    }
+    // missing entries at the end are unmapped
+    keys.resize(size, None);
+
+    Ok(KeyMap {
+        first: header[1] as i32,
+        last: header[2] as i32,
+        middle: header[3] as i32,
+        reference: header[4] as i32,
+        frequency: header[5],
+        octave_degree: header[6] as i32,
+        keys,
+    })
+}
+
+#[cfg(test)]
+fn assert_close(expected: f32, actual: f32) {
+    assert!(
+        (expected - actual).abs() < 0.01,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn test_equal_and_reference() {
+    let mut tuning = Tuning::equal();
+    assert_close(440.0, tuning.frequency(A4_KEY));
+    assert_close(261.63, tuning.frequency(MIDDLE_C_KEY));
+
+    tuning.set_reference(432.0);
+    assert_close(864.0, tuning.frequency(A4_KEY + 12));
+}
+
+#[test]
+fn test_just_intonation() {
+    let tuning = Tuning::from_name("just", MIDDLE_C_KEY).unwrap();
+    // the major sixth above c is 5/3, a stays at 440
+    assert_close(264.0, tuning.frequency(MIDDLE_C_KEY));
+    assert_close(396.0, tuning.frequency(MIDDLE_C_KEY + 7));
+}
+
+#[test]
+fn test_scala_files() {
+    let scl =
+        "! slendro.scl\n!\nFive note slendro\n 5\n!\n 240.0\n 480.0 cents\n 720.0\n 960.0\n 2/1\n";
+    let kbm =
+        "! maps the five notes on the black keys\n5\n0\n127\n61\n61\n277.2\n5\n0\n1\n2\n3\n4\n";
+    let tuning = Tuning::from_scala(scl, Some(kbm)).unwrap();
+    assert_close(277.2, tuning.frequency(61));
+    assert_close(277.2 * 2.0_f32.powf(0.2), tuning.frequency(62));
+    assert_close(554.4, tuning.frequency(66));
+
+    assert!(Tuning::from_scala("bad\nfive\n", None).is_err());
+    assert!(Tuning::from_scala("two notes\n2\n3/2\n", None).is_err());
+}